@@ -17,13 +17,27 @@ struct HeightRange {
     height: u32
 }
 
+/// The strategy used to choose where an image gets inserted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InsertHeuristic {
+    /// Insert images as low as possible, preferring the leftmost position when there's a tie. Fast
+    /// and predictable, but can leave gaps beneath tall images.
+    BottomLeft,
+    /// Insert images wherever the least space gets wasted beneath them, and reuse the free rect
+    /// that the image fills most closely.
+    BestFit
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SkylineAtlas<P: Copy> {
     background_color: P,
     dims: DimsBox<Point2<u32>>,
     pixels: Vec<P>,
     heights: Vec<HeightRange>,
-    max_used_height: u32
+    max_used_height: u32,
+    /// Space beneath the skyline that was freed by removing images.
+    free_rects: Vec<OffsetBox<Point2<u32>>>,
+    heuristic: InsertHeuristic
 }
 
 #[derive(Debug, Clone)]
@@ -47,10 +61,24 @@ impl<P: Copy> SkylineAtlas<P> {
             background_color, dims,
             pixels: vec![background_color; (dims.width() * dims.height()) as usize],
             heights: vec![base_range],
-            max_used_height: 0
+            max_used_height: 0,
+            free_rects: Vec::new(),
+            heuristic: InsertHeuristic::BestFit
         }
     }
 
+    #[inline]
+    pub fn heuristic(&self) -> InsertHeuristic {
+        self.heuristic
+    }
+
+    /// Set the strategy used to choose where new images get inserted. Images that are already in
+    /// the atlas aren't moved.
+    #[inline]
+    pub fn set_heuristic(&mut self, heuristic: InsertHeuristic) {
+        self.heuristic = heuristic;
+    }
+
     #[inline]
     pub fn pixels(&self) -> &[P] {
         &self.pixels
@@ -102,10 +130,12 @@ impl<P: Copy> SkylineAtlas<P> {
             }
 
             let active_is_better =
-                (
-                    active_range.space_lost < best_range.space_lost ||
-                    active_range.height < best_range.height
-                ) &&
+                match self.heuristic {
+                    InsertHeuristic::BottomLeft => active_range.height < best_range.height,
+                    InsertHeuristic::BestFit =>
+                        active_range.space_lost < best_range.space_lost ||
+                        active_range.height < best_range.height
+                } &&
                 active_range.width >= image_dims.width() &&
                 self.dims.height() - active_range.height >= image_dims.height();
             if active_is_better {
@@ -184,9 +214,8 @@ impl<P: Copy> SkylineAtlas<P> {
         where I: IntoIterator<Item=&'a [P]>,
               P: 'a
     {
-        match self.calc_insert_over(image_dims) {
-            Some(range) => {
-                let insert_rect = self.insert_over(range, image_dims);
+        match self.alloc(image_dims) {
+            Some(insert_rect) => {
                 self.blit_rows(image_dims, insert_rect.min().to_vec(), image_data);
                 Ok(insert_rect)
             },
@@ -198,9 +227,8 @@ impl<P: Copy> SkylineAtlas<P> {
         where I: IntoIterator<Item=J>,
               J: IntoIterator<Item=P>
     {
-        match self.calc_insert_over(image_dims) {
-            Some(range) =>{
-                let insert_rect = self.insert_over(range, image_dims);
+        match self.alloc(image_dims) {
+            Some(insert_rect) => {
                 self.blit_pixels(image_dims, insert_rect.min().to_vec(), image_data);
                 Ok(insert_rect)
             },
@@ -208,7 +236,92 @@ impl<P: Copy> SkylineAtlas<P> {
         }
     }
 
+    /// Find space for an image, preferring space freed by `remove_image` over space above the
+    /// skyline.
+    fn alloc(&mut self, image_dims: DimsBox<Point2<u32>>) -> Option<OffsetBox<Point2<u32>>> {
+        match self.alloc_free_rect(image_dims) {
+            Some(rect) => Some(rect),
+            None => {
+                let range = self.calc_insert_over(image_dims)?;
+                Some(self.insert_over(range, image_dims))
+            }
+        }
+    }
+
+    /// Find space for an image in an atlas that doesn't contain any images yet. The image is placed
+    /// in the atlas's corner, so this succeeds as long as the atlas is at least as large as the
+    /// image.
+    fn alloc_empty(&mut self, image_dims: DimsBox<Point2<u32>>) -> OffsetBox<Point2<u32>> {
+        debug_assert!(self.heights.len() == 1 && self.heights[0].height == 0);
+        debug_assert!(image_dims.width() <= self.dims.width() && image_dims.height() <= self.dims.height());
+        let insert_over = InsertOver {
+            range: 0..1,
+            width: self.dims.width(),
+            height: 0,
+            space_lost: 0
+        };
+        self.insert_over(insert_over, image_dims)
+    }
+
+    fn alloc_free_rect(&mut self, image_dims: DimsBox<Point2<u32>>) -> Option<OffsetBox<Point2<u32>>> {
+        let heuristic = self.heuristic;
+        let (free_index, free_rect) = self.free_rects.iter().cloned().enumerate()
+            .filter(|&(_, rect)| rect.width() >= image_dims.width() && rect.height() >= image_dims.height())
+            .min_by_key(|&(_, rect)| match heuristic {
+                InsertHeuristic::BottomLeft => (rect.min().y, rect.min().x),
+                InsertHeuristic::BestFit => (rect.width() * rect.height() - image_dims.width() * image_dims.height(), 0)
+            })?;
+        self.free_rects.swap_remove(free_index);
+
+        // Split the space left over in the free rect into a rect to the right of the image and a
+        // rect beneath it, giving the larger of the two the free rect's full width or height.
+        let (free_min, free_max) = (free_rect.min(), free_rect.max());
+        let (image_max_x, image_max_y) = (free_min.x + image_dims.width(), free_min.y + image_dims.height());
+        let (right, below) = match free_rect.width() - image_dims.width() > free_rect.height() - image_dims.height() {
+            true => (
+                OffsetBox::new2(image_max_x, free_min.y, free_max.x - image_max_x, free_rect.height()),
+                OffsetBox::new2(free_min.x, image_max_y, image_dims.width(), free_max.y - image_max_y)
+            ),
+            false => (
+                OffsetBox::new2(image_max_x, free_min.y, free_max.x - image_max_x, image_dims.height()),
+                OffsetBox::new2(free_min.x, image_max_y, free_rect.width(), free_max.y - image_max_y)
+            )
+        };
+        for rect in Some(right).into_iter().chain(Some(below)) {
+            if rect.width() > 0 && rect.height() > 0 {
+                self.free_rects.push(rect);
+            }
+        }
+
+        Some(OffsetBox::new2(free_min.x, free_min.y, image_dims.width(), image_dims.height()))
+    }
+
+    /// Remove an image from the atlas, letting its space be reused by later insertions. `rect`
+    /// must be a rect returned when adding an image, which hasn't already been removed.
+    pub fn remove_image(&mut self, rect: OffsetBox<Point2<u32>>) {
+        let background_color = self.background_color;
+        let dims = self.dims;
+        for row in rect.min().y..rect.max().y {
+            let row_start = (row * dims.width()) as usize;
+            for pixel in &mut self.pixels[row_start + rect.min().x as usize..row_start + rect.max().x as usize] {
+                *pixel = background_color;
+            }
+        }
+
+        // Merge the freed rect with any free rects it shares a full edge with, so that images
+        // larger than any single removed image can fit in the freed space.
+        let mut rect = rect;
+        while let Some(merge_index) = self.free_rects.iter().position(|free| shares_edge(*free, rect)) {
+            let free = self.free_rects.swap_remove(merge_index);
+            let min = Point2::new(cmp::min(free.min().x, rect.min().x), cmp::min(free.min().y, rect.min().y));
+            let max = Point2::new(cmp::max(free.max().x, rect.max().x), cmp::max(free.max().y, rect.max().y));
+            rect = OffsetBox::new2(min.x, min.y, max.x - min.x, max.y - min.y);
+        }
+        self.free_rects.push(rect);
+    }
+
     pub fn clear(&mut self, background_color: Option<P>) {
+        self.free_rects.clear();
         self.heights.clear();
         self.heights.push(HeightRange {
             bounds_min: 0,
@@ -238,6 +351,7 @@ impl<P: Copy> SkylineAtlas<P> {
         let mut removed_rects = Vec::with_capacity(rects_sorted.len());
 
         self.max_used_height = 0;
+        let old_free_rects = mem::replace(&mut self.free_rects, Vec::new());
         self.heights.clear();
         self.heights.push(HeightRange {
             bounds_min: 0,
@@ -287,6 +401,7 @@ impl<P: Copy> SkylineAtlas<P> {
         if reset_atlas {
             self.pixels = old_pixels;
             self.heights = old_heights;
+            self.free_rects = old_free_rects;
             for (old_rect, rect_ref) in rects_sorted.drain(..).chain(removed_rects.drain(..)) {
                 *rect_ref = old_rect;
             }
@@ -315,6 +430,136 @@ impl<P: Copy> SkylineAtlas<P> {
     }
 }
 
+/// A set of fixed-size `SkylineAtlas` pages. Images that don't fit in any existing page get added
+/// to a new page, so the atlas never grows past the page size (unless a single image is larger than
+/// a page, in which case that image gets its own page).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PagedAtlas<P: Copy> {
+    background_color: P,
+    page_dims: DimsBox<Point2<u32>>,
+    pages: Vec<SkylineAtlas<P>>,
+    heuristic: InsertHeuristic
+}
+
+impl<P: Copy> PagedAtlas<P> {
+    #[inline]
+    pub fn new(background_color: P, page_dims: DimsBox<Point2<u32>>) -> PagedAtlas<P> {
+        PagedAtlas {
+            background_color, page_dims,
+            pages: vec![SkylineAtlas::new(background_color, page_dims)],
+            heuristic: InsertHeuristic::BestFit
+        }
+    }
+
+    #[inline]
+    pub fn heuristic(&self) -> InsertHeuristic {
+        self.heuristic
+    }
+
+    /// Set the strategy used to choose where new images get inserted in each page.
+    pub fn set_heuristic(&mut self, heuristic: InsertHeuristic) {
+        self.heuristic = heuristic;
+        for page in &mut self.pages {
+            page.set_heuristic(heuristic);
+        }
+    }
+
+    #[inline]
+    pub fn page_dims(&self) -> DimsBox<Point2<u32>> {
+        self.page_dims
+    }
+
+    #[inline]
+    pub fn pages(&self) -> &[SkylineAtlas<P>] {
+        &self.pages
+    }
+
+    fn new_page(&mut self, image_dims: DimsBox<Point2<u32>>) -> &mut SkylineAtlas<P> {
+        let page_dims = DimsBox::new2(
+            cmp::max(self.page_dims.width(), image_dims.width()),
+            cmp::max(self.page_dims.height(), image_dims.height())
+        );
+        let mut page = SkylineAtlas::new(self.background_color, page_dims);
+        page.set_heuristic(self.heuristic);
+        self.pages.push(page);
+        self.pages.last_mut().unwrap()
+    }
+
+    /// Remove an image from the given page, letting its space be reused by later insertions.
+    #[inline]
+    pub fn remove_image(&mut self, page: usize, rect: OffsetBox<Point2<u32>>) {
+        self.pages[page].remove_image(rect);
+    }
+
+    /// Add an image to the first page it fits in, returning the page's index along with the
+    /// image's rect within the page.
+    pub fn add_image(&mut self, image_dims: DimsBox<Point2<u32>>, image_view: OffsetBox<Point2<u32>>, image_data: &[P]) -> (usize, OffsetBox<Point2<u32>>) {
+        for (page_index, page) in self.pages.iter_mut().enumerate() {
+            if let Some(rect) = page.add_image(image_dims, image_view, image_data) {
+                return (page_index, rect);
+            }
+        }
+
+        // New pages grow to fit images larger than the page size, so the image always fits.
+        let page_index = self.pages.len();
+        let page = self.new_page(DimsBox::new(image_view.dims()));
+        let rect = page.alloc_empty(DimsBox::new(image_view.dims()));
+        page.blit(image_dims, image_view, rect.min().to_vec(), image_data);
+        (page_index, rect)
+    }
+
+    /// Add an image to the first page it fits in, returning the page's index along with the
+    /// image's rect within the page.
+    pub fn add_image_pixels<'a, I, J>(&mut self, image_dims: DimsBox<Point2<u32>>, mut image_data: I) -> (usize, OffsetBox<Point2<u32>>)
+        where I: IntoIterator<Item=J>,
+              J: IntoIterator<Item=P>
+    {
+        for (page_index, page) in self.pages.iter_mut().enumerate() {
+            match page.add_image_pixels(image_dims, image_data) {
+                Ok(rect) => return (page_index, rect),
+                Err(data) => image_data = data
+            }
+        }
+
+        // New pages grow to fit images larger than the page size, so the image always fits.
+        let page_index = self.pages.len();
+        let page = self.new_page(image_dims);
+        let rect = page.alloc_empty(image_dims);
+        page.blit_pixels(image_dims, rect.min().to_vec(), image_data);
+        (page_index, rect)
+    }
+
+    /// Remove every image from the atlas, leaving a single empty page.
+    pub fn clear(&mut self, background_color: Option<P>) {
+        self.pages.truncate(1);
+        if self.pages[0].dims() != self.page_dims {
+            self.pages[0] = SkylineAtlas::new(self.background_color, self.page_dims);
+            self.pages[0].set_heuristic(self.heuristic);
+        }
+        self.pages[0].clear(background_color);
+    }
+
+    /// Compact each page, as with `SkylineAtlas::compact`. Rects aren't moved between pages, but
+    /// empty pages at the end of the atlas get removed.
+    pub fn compact<'a, I>(&mut self, rects: I)
+        where I: IntoIterator<Item=(usize, &'a mut OffsetBox<Point2<u32>>)>
+    {
+        let mut page_rects: Vec<Vec<&'a mut OffsetBox<Point2<u32>>>> = (0..self.pages.len()).map(|_| Vec::new()).collect();
+        for (page_index, rect) in rects {
+            page_rects[page_index].push(rect);
+        }
+
+        while self.pages.len() > 1 && page_rects.last().map(|r| r.is_empty()).unwrap_or(false) {
+            self.pages.pop();
+            page_rects.pop();
+        }
+
+        for (page, rects) in self.pages.iter_mut().zip(page_rects) {
+            page.compact(rects);
+        }
+    }
+}
+
 impl HeightRange {
     #[inline]
     fn width(&self) -> u32 {
@@ -322,6 +567,14 @@ impl HeightRange {
     }
 }
 
+/// Whether two rects share an entire edge, and can be merged into a single rect.
+fn shares_edge(a: OffsetBox<Point2<u32>>, b: OffsetBox<Point2<u32>>) -> bool {
+    let same_columns = a.min().x == b.min().x && a.max().x == b.max().x;
+    let same_rows = a.min().y == b.min().y && a.max().y == b.max().y;
+    (same_columns && (a.max().y == b.min().y || b.max().y == a.min().y)) ||
+    (same_rows && (a.max().x == b.min().x || b.max().x == a.min().x))
+}
+
 fn rows_from_image<'a, P: 'a>(image_dims: DimsBox<Point2<u32>>, image_view: OffsetBox<Point2<u32>>, image_data: &'a [P]) -> impl Iterator<Item=&'a [P]> {
     (image_view.min().y as usize..image_view.max().y as usize)
         .map(move |r| &image_data[
@@ -377,3 +630,141 @@ fn blit_pixels<'a, P, I, J>(
 
     assert_eq!(src_dims, DimsBox::new2(width, height));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add_solid(atlas: &mut SkylineAtlas<u8>, width: u32, height: u32, color: u8) -> Option<OffsetBox<Point2<u32>>> {
+        let dims = DimsBox::new2(width, height);
+        atlas.add_image(dims, dims.into(), &vec![color; (width * height) as usize])
+    }
+
+    fn pixel(atlas: &SkylineAtlas<u8>, x: u32, y: u32) -> u8 {
+        atlas.pixels()[(y * atlas.dims().width() + x) as usize]
+    }
+
+    #[test]
+    fn reinsert_removed() {
+        let mut atlas = SkylineAtlas::new(0, DimsBox::new2(16, 16));
+        let a = add_solid(&mut atlas, 4, 4, 1).unwrap();
+        let b = add_solid(&mut atlas, 4, 4, 2).unwrap();
+        assert_eq!(OffsetBox::new2(0, 0, 4, 4), a);
+        assert_eq!(OffsetBox::new2(4, 0, 4, 4), b);
+
+        atlas.remove_image(a);
+        assert_eq!(0, pixel(&atlas, 0, 0));
+        assert_eq!(2, pixel(&atlas, 4, 0));
+
+        let c = add_solid(&mut atlas, 4, 4, 3).unwrap();
+        assert_eq!(a, c);
+        assert_eq!(3, pixel(&atlas, 0, 0));
+        assert_eq!(3, pixel(&atlas, 3, 3));
+        assert!(atlas.free_rects.is_empty());
+    }
+
+    #[test]
+    fn merge_adjacent_removed() {
+        let mut atlas = SkylineAtlas::new(0, DimsBox::new2(16, 16));
+        let a = add_solid(&mut atlas, 4, 4, 1).unwrap();
+        let b = add_solid(&mut atlas, 4, 4, 2).unwrap();
+
+        atlas.remove_image(a);
+        atlas.remove_image(b);
+        assert_eq!(vec![OffsetBox::new2(0, 0, 8, 4)], atlas.free_rects);
+
+        // The merged rect is wide enough to fit an image that neither removed rect could.
+        let c = add_solid(&mut atlas, 8, 4, 3).unwrap();
+        assert_eq!(OffsetBox::new2(0, 0, 8, 4), c);
+        assert!(atlas.free_rects.is_empty());
+    }
+
+    #[test]
+    fn free_rect_heuristics() {
+        let mut atlas = SkylineAtlas::new(0, DimsBox::new2(16, 16));
+        let a = add_solid(&mut atlas, 8, 8, 1).unwrap();
+        let b = add_solid(&mut atlas, 4, 4, 2).unwrap();
+        assert_eq!(OffsetBox::new2(0, 0, 8, 8), a);
+        assert_eq!(OffsetBox::new2(8, 0, 4, 4), b);
+        atlas.remove_image(a);
+        atlas.remove_image(b);
+        assert_eq!(2, atlas.free_rects.len());
+
+        // Best fit fills the free rect that matches the image exactly...
+        let mut best_fit = atlas.clone();
+        best_fit.set_heuristic(InsertHeuristic::BestFit);
+        assert_eq!(Some(OffsetBox::new2(8, 0, 4, 4)), add_solid(&mut best_fit, 4, 4, 3));
+
+        // ...while bottom-left picks the leftmost of the lowest free rects.
+        let mut bottom_left = atlas.clone();
+        bottom_left.set_heuristic(InsertHeuristic::BottomLeft);
+        assert_eq!(Some(OffsetBox::new2(0, 0, 4, 4)), add_solid(&mut bottom_left, 4, 4, 3));
+    }
+
+    fn add_solid_paged(atlas: &mut PagedAtlas<u8>, width: u32, height: u32, color: u8) -> (usize, OffsetBox<Point2<u32>>) {
+        atlas.add_image_pixels(DimsBox::new2(width, height), vec![vec![color; width as usize]; height as usize])
+    }
+
+    fn paged_pixel(atlas: &PagedAtlas<u8>, page: usize, x: u32, y: u32) -> u8 {
+        pixel(&atlas.pages()[page], x, y)
+    }
+
+    #[test]
+    fn paged_rollover() {
+        let mut atlas = PagedAtlas::new(0, DimsBox::new2(8, 8));
+        assert_eq!((0, OffsetBox::new2(0, 0, 8, 4)), add_solid_paged(&mut atlas, 8, 4, 1));
+        assert_eq!((0, OffsetBox::new2(0, 4, 8, 4)), add_solid_paged(&mut atlas, 8, 4, 2));
+        assert_eq!((1, OffsetBox::new2(0, 0, 8, 8)), add_solid_paged(&mut atlas, 8, 8, 3));
+        assert_eq!(2, atlas.pages().len());
+        assert_eq!(3, paged_pixel(&atlas, 1, 7, 7));
+    }
+
+    #[test]
+    fn paged_oversize() {
+        let mut atlas = PagedAtlas::new(0, DimsBox::new2(8, 8));
+        assert_eq!((1, OffsetBox::new2(0, 0, 16, 4)), add_solid_paged(&mut atlas, 16, 4, 1));
+        assert_eq!(DimsBox::new2(16, 8), atlas.pages()[1].dims());
+        assert_eq!(1, paged_pixel(&atlas, 1, 15, 3));
+
+        let dims = DimsBox::new2(4, 12);
+        assert_eq!((2, OffsetBox::new2(0, 0, 4, 12)), atlas.add_image(dims, dims.into(), &[2; 4 * 12]));
+        assert_eq!(DimsBox::new2(8, 12), atlas.pages()[2].dims());
+    }
+
+    #[test]
+    fn paged_compact() {
+        let mut atlas = PagedAtlas::new(0, DimsBox::new2(8, 8));
+        let (_, mut a) = add_solid_paged(&mut atlas, 8, 4, 1);
+        let (_, mut b) = add_solid_paged(&mut atlas, 8, 4, 2);
+        let (_, mut c) = add_solid_paged(&mut atlas, 8, 8, 3);
+
+        // Dropping `a` lets `b` move into its space, while `c` keeps its page.
+        let mut compacted = atlas.clone();
+        compacted.compact(vec![(0, &mut b), (1, &mut c)]);
+        assert_eq!(OffsetBox::new2(0, 0, 8, 4), b);
+        assert_eq!(OffsetBox::new2(0, 0, 8, 8), c);
+        assert_eq!(2, paged_pixel(&compacted, 0, 0, 0));
+        assert_eq!(0, paged_pixel(&compacted, 0, 0, 4));
+        assert_eq!(3, paged_pixel(&compacted, 1, 0, 0));
+        assert_eq!(2, compacted.pages().len());
+
+        // Empty pages at the end of the atlas get removed.
+        atlas.compact(vec![(0, &mut a)]);
+        assert_eq!(OffsetBox::new2(0, 0, 8, 4), a);
+        assert_eq!(1, paged_pixel(&atlas, 0, 0, 0));
+        assert_eq!(1, atlas.pages().len());
+    }
+
+    #[test]
+    fn paged_clear() {
+        let mut atlas = PagedAtlas::new(0, DimsBox::new2(8, 8));
+        add_solid_paged(&mut atlas, 8, 8, 1);
+        add_solid_paged(&mut atlas, 8, 8, 2);
+        assert_eq!(2, atlas.pages().len());
+
+        atlas.clear(Some(0));
+        assert_eq!(1, atlas.pages().len());
+        assert_eq!(0, paged_pixel(&atlas, 0, 0, 0));
+        assert_eq!((0, OffsetBox::new2(0, 0, 8, 8)), add_solid_paged(&mut atlas, 8, 8, 3));
+    }
+}
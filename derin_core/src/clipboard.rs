@@ -0,0 +1,83 @@
+use std::fmt;
+
+/// Which clipboard buffer to access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClipboardKind {
+    /// The standard clipboard, used for explicit copy, cut and paste operations.
+    Clipboard,
+    /// The primary selection, which holds the most recently selected text and is pasted by
+    /// middle-clicking.
+    ///
+    /// This is only supported on X11. Providers without a primary selection should ignore writes
+    /// to it and return `None` when it's read.
+    Primary
+}
+
+/// A source and sink for text copied and pasted by widgets.
+///
+/// The `Root` owns a single clipboard, which every widget reaches through the `EventContext` passed
+/// to `on_widget_event`, so that all widgets share the same clipboard. Defaults to a `MemoryClipboard`.
+pub trait Clipboard {
+    /// Get the contents of the given clipboard, or `None` if the clipboard is empty or couldn't be
+    /// read.
+    fn get_contents(&mut self, kind: ClipboardKind) -> Option<String>;
+    /// Replace the contents of the given clipboard.
+    fn set_contents(&mut self, kind: ClipboardKind, contents: String);
+}
+
+impl<'a> fmt::Debug for Clipboard + 'a {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Clipboard")
+    }
+}
+
+/// A clipboard that stores its contents in memory, without touching the system clipboard.
+///
+/// Useful for running widgets headless, or in tests.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MemoryClipboard {
+    pub clipboard: Option<String>,
+    pub primary: Option<String>
+}
+
+impl MemoryClipboard {
+    pub fn new() -> MemoryClipboard {
+        MemoryClipboard::default()
+    }
+}
+
+impl Clipboard for MemoryClipboard {
+    fn get_contents(&mut self, kind: ClipboardKind) -> Option<String> {
+        match kind {
+            ClipboardKind::Clipboard => self.clipboard.clone(),
+            ClipboardKind::Primary => self.primary.clone()
+        }
+    }
+
+    fn set_contents(&mut self, kind: ClipboardKind, contents: String) {
+        match kind {
+            ClipboardKind::Clipboard => self.clipboard = Some(contents),
+            ClipboardKind::Primary => self.primary = Some(contents)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_clipboard_buffers() {
+        let mut clipboard = MemoryClipboard::new();
+        assert_eq!(None, clipboard.get_contents(ClipboardKind::Clipboard));
+
+        clipboard.set_contents(ClipboardKind::Clipboard, "copied".to_string());
+        clipboard.set_contents(ClipboardKind::Primary, "selected".to_string());
+        assert_eq!(Some("copied".to_string()), clipboard.get_contents(ClipboardKind::Clipboard));
+        assert_eq!(Some("selected".to_string()), clipboard.get_contents(ClipboardKind::Primary));
+
+        clipboard.set_contents(ClipboardKind::Clipboard, "recopied".to_string());
+        assert_eq!(Some("recopied".to_string()), clipboard.get_contents(ClipboardKind::Clipboard));
+        assert_eq!(Some("selected".to_string()), clipboard.get_contents(ClipboardKind::Primary));
+    }
+}
@@ -1,12 +1,15 @@
 use dct::cursor::CursorIcon;
 use dct::buttons::{MouseButton, Key, ModifierKeys};
 use cgmath::{Point2, Vector2};
+use cgmath_geometry::BoundBox;
 use tree::{Widget, WidgetIdent};
 use arrayvec::ArrayVec;
 use mbseq::{MouseButtonSequence, MouseButtonSequenceTrackPos};
 use render::RenderFrame;
 use popup::PopupAttributes;
+use clipboard::Clipboard;
 
+use std::fmt;
 use std::time::{Instant, Duration};
 
 /// The set of operations to be performed after an event is processed by a widget.
@@ -29,6 +32,8 @@ pub struct EventOps<A, F: RenderFrame> {
     /// Note that this change is permanent, and isn't reset to the default cursor until another
     /// `cursor_icon` operation is recieved.
     pub cursor_icon: Option<CursorIcon>,
+    /// Report the rectangle of the widget's text caret, used to position the IME candidate window.
+    pub ime_caret_rect: Option<BoundBox<Point2<i32>>>,
     /// Create a popup window with the given attributes.
     ///
     /// This *does not count as a child widget*, and events bubbled from the popup will not be
@@ -74,6 +79,33 @@ pub struct InputState<'a> {
     pub modifiers: ModifierKeys
 }
 
+/// Services provided by the root to widgets handling an event.
+///
+/// Services are reached through methods on the context, so that new ones can be added without
+/// changing the signature of `on_widget_event`.
+pub struct EventContext<'a> {
+    clipboard: &'a mut Clipboard
+}
+
+impl<'a> EventContext<'a> {
+    pub(crate) fn new(clipboard: &'a mut Clipboard) -> EventContext<'a> {
+        EventContext{ clipboard }
+    }
+
+    /// The clipboard shared by all of the root's widgets.
+    pub fn clipboard(&mut self) -> &mut Clipboard {
+        &mut *self.clipboard
+    }
+}
+
+impl<'a> fmt::Debug for EventContext<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EventContext")
+            .field("clipboard", &self.clipboard)
+            .finish()
+    }
+}
+
 /// Direct user input and timers, which are recieved and handled by widgets through the
 /// `on_widget_event` function.
 ///
@@ -157,6 +189,14 @@ pub enum WidgetEvent<'a> {
         /// The button that was released.
         button: MouseButton
     },
+    /// The mouse wheel has been scrolled by the given number of lines.
+    ///
+    /// Positive values scroll up and to the right.
+    MouseScrollLines(Vector2<i32>),
+    /// The mouse wheel or touchpad has been scrolled by the given number of pixels.
+    ///
+    /// Positive values scroll up and to the right.
+    MouseScrollPx(Vector2<i32>),
     /// The widget has gained keyboard focus.
     GainFocus,
     /// The widget has lost keyboard focus.
@@ -166,6 +206,19 @@ pub enum WidgetEvent<'a> {
     /// This includes the effects of any modifier keys on the character - for example, if the `A` key
     /// is pressed while `Shift` is being held down, this will give the `'A'` character.
     Char(char),
+    /// The input method's uncommitted composition (preedit) text has changed.
+    ///
+    /// Composition text should be displayed at the cursor, but not treated as part of the widget's
+    /// text until an `ImeCommit` event is recieved. An empty `text` means composition has been
+    /// cancelled.
+    ImePreedit {
+        /// The text being composed.
+        text: &'a str,
+        /// The byte range of the IME's cursor within `text`, if the IME displays one.
+        cursor: Option<(usize, usize)>
+    },
+    /// The input method has committed the given text, ending composition.
+    ImeCommit(&'a str),
     /// The given key has been pressed on the keyboard.
     KeyDown(Key, ModifierKeys),
     /// The given key has been released on the keyboard.
@@ -189,7 +242,7 @@ pub enum WidgetEvent<'a> {
 }
 
 /// Non-borrowing equivalents of the `WidgetEvent` enum.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) enum WidgetEventOwned {
     MouseEnter {
         enter_pos: Point2<i32>,
@@ -232,9 +285,16 @@ pub(crate) enum WidgetEventOwned {
         down_pos: Point2<i32>,
         button: MouseButton
     },
+    MouseScrollLines(Vector2<i32>),
+    MouseScrollPx(Vector2<i32>),
     GainFocus,
     LoseFocus,
     Char(char),
+    ImePreedit {
+        text: String,
+        cursor: Option<(usize, usize)>
+    },
+    ImeCommit(String),
     KeyDown(Key, ModifierKeys),
     KeyUp(Key, ModifierKeys),
     Timer {
@@ -292,9 +352,13 @@ impl<'a> WidgetEvent<'a> {
                     down_pos: down_pos + dir,
                     in_widget, pressed_in_widget, button
                 },
+            WidgetEvent::MouseScrollLines(dir) => WidgetEvent::MouseScrollLines(dir),
+            WidgetEvent::MouseScrollPx(dir) => WidgetEvent::MouseScrollPx(dir),
             WidgetEvent::GainFocus => WidgetEvent::GainFocus,
             WidgetEvent::LoseFocus => WidgetEvent::LoseFocus,
             WidgetEvent::Char(c) => WidgetEvent::Char(c),
+            WidgetEvent::ImePreedit{ text, cursor } => WidgetEvent::ImePreedit{ text, cursor },
+            WidgetEvent::ImeCommit(text) => WidgetEvent::ImeCommit(text),
             WidgetEvent::KeyDown(k, modifiers) => WidgetEvent::KeyDown(k, modifiers),
             WidgetEvent::KeyUp(k, modifiers) => WidgetEvent::KeyUp(k, modifiers),
             WidgetEvent::Timer{ name, start_time, last_trigger, frequency, times_triggered } =>
@@ -362,9 +426,13 @@ impl WidgetEventOwned {
                 WidgetEvent::MouseUp {
                     pos, down_pos, in_widget, pressed_in_widget, button
                 },
+            WidgetEventOwned::MouseScrollLines(dir) => WidgetEvent::MouseScrollLines(dir),
+            WidgetEventOwned::MouseScrollPx(dir) => WidgetEvent::MouseScrollPx(dir),
             WidgetEventOwned::GainFocus => WidgetEvent::GainFocus,
             WidgetEventOwned::LoseFocus => WidgetEvent::LoseFocus,
             WidgetEventOwned::Char(c) => WidgetEvent::Char(c),
+            WidgetEventOwned::ImePreedit{ ref text, cursor } => WidgetEvent::ImePreedit{ text, cursor },
+            WidgetEventOwned::ImeCommit(ref text) => WidgetEvent::ImeCommit(text),
             WidgetEventOwned::KeyDown(k, modifiers) => WidgetEvent::KeyDown(k, modifiers),
             WidgetEventOwned::KeyUp(k, modifiers) => WidgetEvent::KeyUp(k, modifiers),
             WidgetEventOwned::Timer{ name, start_time, last_trigger, frequency, times_triggered } =>
@@ -431,9 +499,13 @@ impl<'a> From<WidgetEvent<'a>> for WidgetEventOwned {
                 WidgetEventOwned::MouseUp {
                     pos, in_widget, pressed_in_widget, down_pos, button
                 },
+            WidgetEvent::MouseScrollLines(dir) => WidgetEventOwned::MouseScrollLines(dir),
+            WidgetEvent::MouseScrollPx(dir) => WidgetEventOwned::MouseScrollPx(dir),
             WidgetEvent::GainFocus => WidgetEventOwned::GainFocus,
             WidgetEvent::LoseFocus => WidgetEventOwned::LoseFocus,
             WidgetEvent::Char(c) => WidgetEventOwned::Char(c),
+            WidgetEvent::ImePreedit{ text, cursor } => WidgetEventOwned::ImePreedit{ text: text.to_string(), cursor },
+            WidgetEvent::ImeCommit(text) => WidgetEventOwned::ImeCommit(text.to_string()),
             WidgetEvent::KeyDown(k, modifiers) => WidgetEventOwned::KeyDown(k, modifiers),
             WidgetEvent::KeyUp(k, modifiers) => WidgetEventOwned::KeyUp(k, modifiers),
             WidgetEvent::Timer{ name, start_time, last_trigger, frequency, times_triggered } =>
@@ -9,7 +9,7 @@ use {WindowEvent, LoopFlow, Root};
 use tree::*;
 use timer::Timer;
 use popup::{PopupSummary, PopupID};
-use event::{WidgetEvent, InputState, MouseDown, FocusChange};
+use event::{WidgetEvent, InputState, EventContext, MouseDown, FocusChange};
 use render::{Renderer, RenderFrame, FrameRectStack};
 use widget_stack::{WidgetPath, WidgetStack};
 use meta_tracker::{MetaDrain, MetaEvent, MetaEventVariant};
@@ -75,6 +75,7 @@ impl<'a, A, N, F, R, G> EventLoopOps<'a, A, N, F, R, G>
                 ref mut timer_list,
                 ref mut root_widget,
                 ref mut theme,
+                ref mut clipboard,
                 ref mut popup_widgets,
                 ref mut modifiers,
                 ref mut cursor_icon,
@@ -91,6 +92,7 @@ impl<'a, A, N, F, R, G> EventLoopOps<'a, A, N, F, R, G>
 
         let mut set_cursor_pos = None;
         let mut set_cursor_icon = None;
+        let mut set_ime_caret_rect = None;
         let mouse_pos: &mut Point2<i32>;
 
         // If we're performing events on a popup, we remove that popup from the popup map so that
@@ -147,6 +149,7 @@ impl<'a, A, N, F, R, G> EventLoopOps<'a, A, N, F, R, G>
                 let event_ops = $widget.on_widget_event(
                     event,
                     input_state,
+                    &mut EventContext::new(&mut **clipboard),
                     popup_widgets.popups_owned_by_mut(widget_id),
                     if_tokens!(($($bubble_path)*) {
                         $($bubble_path)*
@@ -179,6 +182,7 @@ impl<'a, A, N, F, R, G> EventLoopOps<'a, A, N, F, R, G>
                 }
                 set_cursor_pos = set_cursor_pos.or(event_ops.cursor_pos.map(|p| p + widget_offset));
                 set_cursor_icon = set_cursor_icon.or(event_ops.cursor_icon);
+                set_ime_caret_rect = set_ime_caret_rect.or(event_ops.ime_caret_rect.map(|r| r + widget_offset));
                 $(*$bubble_store = event_ops.bubble;)*
 
                 widget_update_tag.last_event_stamp.set(*event_stamp);
@@ -644,6 +648,18 @@ impl<'a, A, N, F, R, G> EventLoopOps<'a, A, N, F, R, G>
                     update_tag.child_event_recv.set(update_tag.child_event_recv.get() & !button_mask);
                 }
             },
+            WindowEvent::MouseScrollLines(dir) => {
+                widget_stack.move_over_flags(ChildEventRecv::MOUSE_HOVER, |widget, path, top_parent_offset| {
+                    let widget_offset = widget.rect().min().to_vec() + top_parent_offset;
+                    try_push_action!(widget, path.iter().cloned(), (_, _) => WidgetEvent::MouseScrollLines(dir), to_rootspace: widget_offset)
+                });
+            },
+            WindowEvent::MouseScrollPx(dir) => {
+                widget_stack.move_over_flags(ChildEventRecv::MOUSE_HOVER, |widget, path, top_parent_offset| {
+                    let widget_offset = widget.rect().min().to_vec() + top_parent_offset;
+                    try_push_action!(widget, path.iter().cloned(), (_, _) => WidgetEvent::MouseScrollPx(dir), to_rootspace: widget_offset)
+                });
+            },
             WindowEvent::KeyDown(key) => {
                 if let Some(WidgetPath{ widget: focus_widget, path, top_parent_offset }) = widget_stack.move_to_keyboard_focus() {
                     let bounds_rootspace = focus_widget.rect() + top_parent_offset;
@@ -668,6 +684,22 @@ impl<'a, A, N, F, R, G> EventLoopOps<'a, A, N, F, R, G>
                     try_push_action!(focus_widget, path.iter().cloned(), (_, _) => WidgetEvent::Char(c), to_rootspace: widget_offset);
                 }
             },
+            WindowEvent::ImePreedit{text, cursor} => {
+                if let Some(WidgetPath{ widget: focus_widget, path, top_parent_offset }) = widget_stack.move_to_keyboard_focus() {
+                    let bounds_rootspace = focus_widget.rect() + top_parent_offset;
+                    let widget_offset = bounds_rootspace.min().to_vec();
+
+                    try_push_action!(focus_widget, path.iter().cloned(), (_, _) => WidgetEvent::ImePreedit{ text: &text, cursor }, to_rootspace: widget_offset);
+                }
+            },
+            WindowEvent::ImeCommit(text) => {
+                if let Some(WidgetPath{ widget: focus_widget, path, top_parent_offset }) = widget_stack.move_to_keyboard_focus() {
+                    let bounds_rootspace = focus_widget.rect() + top_parent_offset;
+                    let widget_offset = bounds_rootspace.min().to_vec();
+
+                    try_push_action!(focus_widget, path.iter().cloned(), (_, _) => WidgetEvent::ImeCommit(&text), to_rootspace: widget_offset);
+                }
+            },
             WindowEvent::Timer => {
                 let triggered_timers = timer_list.trigger_timers().triggered_timers().to_vec();
 
@@ -899,35 +931,45 @@ impl<'a, A, N, F, R, G> EventLoopOps<'a, A, N, F, R, G>
                     *cursor_icon = set_icon;
                 }
             }
+            if let Some(caret_rect) = set_ime_caret_rect {
+                renderer.set_ime_caret_rect(caret_rect);
+            }
 
 
             // Draw the widget tree.
-            if mark_active_widgets_redraw || *force_full_redraw {
-                let force_full_redraw = *force_full_redraw || renderer.force_full_redraw();
+            let theme_changed = renderer.theme_changed(theme);
+            if mark_active_widgets_redraw || *force_full_redraw || theme_changed {
+                let force_full_redraw = *force_full_redraw || theme_changed || renderer.force_full_redraw();
 
                 root_update.render_self |= force_full_redraw;
                 root_update.update_child |= force_full_redraw;
 
+                redraw_widget.update_size_bounds(theme);
+                if let WidgetSubtraitMut::Parent(root_as_parent) = redraw_widget.subtrait_mut() {
+                    update_widget_layout(root_id, force_full_redraw, theme, root_as_parent);
+                }
+                // Re-laying out the children may have marked them to be re-rendered.
+                root_update.update_child |= redraw_widget.update_tag().needs_update(root_id).update_child;
+
                 if root_update.render_self || root_update.update_child {
                     {
                         let (frame, base_transform) = renderer.make_frame();
                         let mut frame_rect_stack = FrameRectStack::new(frame, base_transform, theme, widget_ident_stack);
 
-                        if let WidgetSubtraitMut::Parent(root_as_parent) = redraw_widget.subtrait_mut() {
-                            update_widget_layout(root_id, force_full_redraw, root_as_parent);
-                        }
-                        if root_update.render_self {
-                            redraw_widget.render(&mut frame_rect_stack);
+                        match root_update.render_self {
+                            true => redraw_widget.render(&mut frame_rect_stack),
+                            false => frame_rect_stack.retain_primitives()
                         }
-                        if root_update.update_child {
-                            if let WidgetSubtraitMut::Parent(root_as_parent) = redraw_widget.subtrait_mut() {
+                        match (root_update.update_child, redraw_widget.subtrait_mut()) {
+                            (true, WidgetSubtraitMut::Parent(root_as_parent)) =>
                                 WidgetRenderer {
                                     root_id: root_id,
                                     frame: frame_rect_stack,
                                     force_full_redraw: force_full_redraw,
                                     theme
-                                }.render_widget_children(root_as_parent)
-                            }
+                                }.render_widget_children(root_as_parent),
+                            (false, WidgetSubtraitMut::Parent(_)) => frame_rect_stack.retain_descendant_primitives(),
+                            (_, WidgetSubtraitMut::Widget(_)) => ()
                         }
                     }
 
@@ -967,19 +1009,65 @@ impl<'a, A, N, F, R, G> EventLoopOps<'a, A, N, F, R, G>
     }
 }
 
-fn update_widget_layout<A, F: RenderFrame>(root_id: RootID, force_full_redraw: bool, widget: &mut Parent<A, F>) -> bool {
+fn update_widget_layout<A, F: RenderFrame>(root_id: RootID, force_full_redraw: bool, theme: &F::Theme, widget: &mut Parent<A, F>) -> bool {
+    let Update {
+        update_child,
+        update_layout,
+        ..
+    } = widget.update_tag().needs_update(root_id);
+
+    // Update the children's size bounds before laying them out, and re-solve the layout if any of
+    // them changed.
+    let mut child_bounds_changed = false;
+    if update_child || update_layout || force_full_redraw {
+        widget.children_mut(&mut |children_summaries| {
+            for summary in children_summaries {
+                let old_size_bounds = summary.widget.size_bounds();
+                summary.widget.update_size_bounds(theme);
+                child_bounds_changed |= summary.widget.size_bounds() != old_size_bounds;
+            }
+
+            LoopFlow::Continue
+        });
+    }
+
     // Loop to re-solve widget layout, if children break their size bounds. Is 0..4 so that
     // it doesn't enter an infinite loop if children can never be properly solved.
     for _ in 0..4 {
         let Update {
-            update_child,
+            mut update_child,
             update_layout,
             ..
         } = widget.update_tag().needs_update(root_id);
 
-        if update_layout || force_full_redraw {
+        if update_layout || child_bounds_changed || force_full_redraw {
+            child_bounds_changed = false;
+
+            let mut old_rects = Vec::new();
+            widget.children_mut(&mut |children_summaries| {
+                old_rects.extend(children_summaries.iter().map(|summary| summary.rect));
+                LoopFlow::Continue
+            });
+
             widget.update_child_layout();
             widget.update_tag().unmark_update_layout();
+
+            // Children that were moved or resized have to be re-drawn, along with everything
+            // inside of them.
+            let mut children_moved = false;
+            widget.children_mut(&mut |children_summaries| {
+                for (summary, old_rect) in children_summaries.iter_mut().zip(old_rects.iter()) {
+                    if summary.rect != *old_rect {
+                        mark_subtree_render(&mut *summary.widget);
+                        children_moved = true;
+                    }
+                }
+                LoopFlow::Continue
+            });
+            if children_moved {
+                widget.update_tag().mark_update_child_immutable();
+                update_child = true;
+            }
         }
 
         let mut children_break_bounds = false;
@@ -992,7 +1080,7 @@ fn update_widget_layout<A, F: RenderFrame>(root_id: RootID, force_full_redraw: b
                     } = *summary;
 
                     if let WidgetSubtraitMut::Parent(child_widget_as_parent) = child_widget.subtrait_mut() {
-                        children_break_bounds |= update_widget_layout(root_id, force_full_redraw, child_widget_as_parent);
+                        children_break_bounds |= update_widget_layout(root_id, force_full_redraw, theme, child_widget_as_parent);
                     }
 
                     child_widget.update_tag().unmark_update_layout();
@@ -1011,6 +1099,19 @@ fn update_widget_layout<A, F: RenderFrame>(root_id: RootID, force_full_redraw: b
     widget.size_bounds().bound_rect(widget_rect) != widget_rect
 }
 
+/// Mark a widget and all of its descendants to be re-rendered.
+fn mark_subtree_render<A, F: RenderFrame>(widget: &mut Widget<A, F>) {
+    widget.update_tag().mark_render_all_immutable();
+    if let WidgetSubtraitMut::Parent(widget_as_parent) = widget.subtrait_mut() {
+        widget_as_parent.children_mut(&mut |children_summaries| {
+            for summary in children_summaries {
+                mark_subtree_render(&mut *summary.widget);
+            }
+            LoopFlow::Continue
+        });
+    }
+}
+
 struct WidgetRenderer<'a, F>
     where F: 'a + RenderFrame
 {
@@ -1048,24 +1149,27 @@ impl<'a, F> WidgetRenderer<'a, F>
                         let mut child_frame = self.frame.enter_child_widget(ident);
                         let mut child_frame = child_frame.enter_child_rect(child_rect);
 
-                        if render_self {
-                            child_widget_as_parent.render(&mut child_frame);
+                        match render_self {
+                            true => child_widget_as_parent.render(&mut child_frame),
+                            false => child_frame.retain_primitives()
                         }
-                        if update_child {
-                            WidgetRenderer {
+                        match update_child {
+                            true => WidgetRenderer {
                                 root_id: self.root_id,
                                 frame: child_frame,
                                 force_full_redraw: self.force_full_redraw,
                                 theme: self.theme
-                            }.render_widget_children(child_widget_as_parent);
+                            }.render_widget_children(child_widget_as_parent),
+                            false => child_frame.retain_descendant_primitives()
                         }
                     },
                     WidgetSubtraitMut::Widget(child_widget) => {
-                        if render_self {
-                            let mut child_frame = self.frame.enter_child_widget(ident);
-                            let mut child_frame = child_frame.enter_child_rect(child_rect);
+                        let mut child_frame = self.frame.enter_child_widget(ident);
+                        let mut child_frame = child_frame.enter_child_rect(child_rect);
 
-                            child_widget.render(&mut child_frame);
+                        match render_self {
+                            true => child_widget.render(&mut child_frame),
+                            false => child_frame.retain_primitives()
                         }
                     }
                 }
@@ -9,6 +9,7 @@ extern crate dct;
 extern crate arrayvec;
 extern crate itertools;
 
+pub mod clipboard;
 pub mod timer;
 #[macro_use]
 pub mod tree;
@@ -20,13 +21,14 @@ mod widget_stack;
 mod meta_tracker;
 mod event_loop_ops;
 
-use cgmath::{Point2, Bounded};
+use cgmath::{Point2, Vector2, Bounded};
 use cgmath_geometry::DimsBox;
 
 use std::marker::PhantomData;
 use std::collections::VecDeque;
 
 use tree::*;
+use clipboard::{Clipboard, MemoryClipboard};
 pub use event_loop_ops::{EventLoopOps, EventLoopResult, PopupDelta};
 use timer::TimerList;
 use event::WidgetEvent;
@@ -58,21 +60,35 @@ pub struct Root<A, N, F>
     timer_list: TimerList,
     pub root_widget: N,
     pub theme: F::Theme,
+    clipboard: Box<Clipboard>,
     popup_widgets: PopupMap<A, F>,
     _marker: PhantomData<*const F>
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum WindowEvent {
     MouseMove(Point2<i32>),
     MouseEnter(Point2<i32>),
     MouseExit(Point2<i32>),
     MouseDown(MouseButton),
     MouseUp(MouseButton),
+    MouseScrollLines(Vector2<i32>),
+    MouseScrollPx(Vector2<i32>),
     WindowResize(DimsBox<Point2<u32>>),
     KeyDown(Key),
     KeyUp(Key),
     Char(char),
+    /// The input method's uncommitted composition text has changed. `cursor` is the byte range
+    /// of the IME's cursor within `text`.
+    ///
+    /// Only sent by backends whose windowing library reports IME composition. Other backends
+    /// deliver committed text through `Char`.
+    ImePreedit {
+        text: String,
+        cursor: Option<(usize, usize)>
+    },
+    /// The input method has committed the given text, ending composition.
+    ImeCommit(String),
     Timer
 }
 
@@ -105,11 +121,18 @@ impl<A, N, F> Root<A, N, F>
             meta_tracker: MetaEventTracker::default(),
             timer_list: TimerList::new(None),
             root_widget, theme,
+            clipboard: Box::new(MemoryClipboard::new()),
             popup_widgets: PopupMap::new(),
             _marker: PhantomData
         }
     }
 
+    /// Replace the clipboard shared by the root's widgets. Defaults to a `MemoryClipboard`.
+    #[inline]
+    pub fn set_clipboard(&mut self, clipboard: Box<Clipboard>) {
+        self.clipboard = clipboard;
+    }
+
     pub fn run_forever<R, G>(
         &mut self,
         mut gen_events: impl FnMut(&mut EventLoopOps<A, N, F, R, G>) -> Option<G>,
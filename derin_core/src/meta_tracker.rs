@@ -25,7 +25,7 @@ pub(crate) enum MetaEventVariant {
     EventBubble(WidgetEventOwned)
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 enum MetaCode {
     FocusChange(FocusChange),
     EventBubble(WidgetEventOwned),
@@ -10,21 +10,60 @@ pub trait Renderer {
     fn force_full_redraw(&self) -> bool {false}
     fn set_cursor_pos(&mut self, pos: Point2<i32>);
     fn set_cursor_icon(&mut self, icon: CursorIcon);
+    /// Set the rectangle of the focused widget's text caret, in window coordinates.
+    ///
+    /// Backends that can position the IME candidate window should place it next to this rect.
+    #[inline]
+    fn set_ime_caret_rect(&mut self, _rect: BoundBox<Point2<i32>>) {}
     fn set_size_bounds(&mut self, size_bounds: SizeBounds);
+    /// Get the region of the window that changed in the last finished frame, as a set of rects in
+    /// window coordinates. This is made up of the rects of widgets that were re-rendered, along with
+    /// the rects they and any removed widgets previously covered. Frames that redraw every widget,
+    /// such as after a resize or theme change, damage the whole window.
+    ///
+    /// Returns `None` if the renderer doesn't track damage, in which case the whole window should
+    /// be assumed to have changed.
+    #[inline]
+    fn damaged_region(&self) -> Option<&[BoundBox<Point2<i32>>]> {None}
+    /// Called with the theme before each frame is drawn. Returns whether the theme has changed since
+    /// the last frame, which forces a full redraw.
+    ///
+    /// Renderers that retain primitives between frames have to detect theme changes, since the
+    /// retained primitives were drawn with the old theme.
+    #[inline]
+    fn theme_changed(&mut self, _theme: &<Self::Frame as RenderFrame>::Theme) -> bool {false}
     fn make_frame(&mut self) -> (&mut Self::Frame, <Self::Frame as RenderFrame>::Transform);
     fn finish_frame(&mut self, theme: &<Self::Frame as RenderFrame>::Theme);
 }
 
-pub trait RenderFrame: 'static {
+pub trait RenderFrame: 'static + for<'a> RenderFramePrimitive<'a> {
     type Transform: Copy;
     type Theme: Theme;
-    type Primitive;
 
-    fn upload_primitives<I>(&mut self, widget_ident: &[WidgetIdent], theme: &Self::Theme, transform: &Self::Transform, prim_iter: I)
-        where I: Iterator<Item=Self::Primitive>;
+    fn upload_primitives<'a, I>(&mut self, widget_ident: &[WidgetIdent], theme: &Self::Theme, transform: &Self::Transform, prim_iter: I)
+        where I: Iterator<Item=<Self as RenderFramePrimitive<'a>>::Primitive>;
+    /// Keep drawing the primitives the widget uploaded in the previous frame, since the widget
+    /// isn't being re-rendered this frame.
+    ///
+    /// Frames that don't retain primitives between frames should have the renderer force a full
+    /// redraw, in which case this never gets called.
+    #[inline]
+    fn retain_primitives(&mut self, _widget_ident: &[WidgetIdent]) {}
+    /// Keep drawing the primitives uploaded in the previous frame by all of the widget's
+    /// descendants, since none of them are being re-rendered this frame.
+    #[inline]
+    fn retain_descendant_primitives(&mut self, _widget_ident: &[WidgetIdent]) {}
     fn child_rect_transform(self_transform: &Self::Transform, child_rect: BoundBox<Point2<i32>>) -> Self::Transform;
 }
 
+/// The primitive type a `RenderFrame` draws.
+///
+/// Primitives can borrow from the widget uploading them for `'a`, so that widgets don't have to
+/// hand the frame raw pointers to their contents.
+pub trait RenderFramePrimitive<'a> {
+    type Primitive: 'a;
+}
+
 pub trait Theme {
     type Key: ?Sized;
     type ThemeValue;
@@ -67,13 +106,23 @@ impl<'a, F: RenderFrame> FrameRectStack<'a, F> {
     }
 
     #[inline]
-    pub fn upload_primitives<I>(&mut self, prim_iter: I)
-        where I: Iterator<Item=F::Primitive>
+    pub fn upload_primitives<'p, I>(&mut self, prim_iter: I)
+        where I: Iterator<Item=<F as RenderFramePrimitive<'p>>::Primitive>
     {
         let widget_ident = &self.widget_ident;
         self.frame.upload_primitives(widget_ident, self.theme, &self.transform, prim_iter)
     }
 
+    #[inline]
+    pub(crate) fn retain_primitives(&mut self) {
+        self.frame.retain_primitives(&self.widget_ident);
+    }
+
+    #[inline]
+    pub(crate) fn retain_descendant_primitives(&mut self) {
+        self.frame.retain_descendant_primitives(&self.widget_ident);
+    }
+
     #[inline]
     pub fn enter_child_rect<'b>(&'b mut self, child_rect: BoundBox<Point2<i32>>) -> FrameRectStack<'b, F> {
         FrameRectStack {
@@ -7,7 +7,7 @@ use cgmath_geometry::BoundBox;
 use mbseq::MouseButtonSequence;
 use dct::buttons::MouseButton;
 use dct::layout::SizeBounds;
-use event::{WidgetEvent, EventOps, InputState};
+use event::{WidgetEvent, EventOps, InputState, EventContext};
 use render::{RenderFrame, FrameRectStack};
 use timer::TimerRegister;
 use popup::ChildPopupsMut;
@@ -216,6 +216,7 @@ pub trait Widget<A, F: RenderFrame> {
         &mut self,
         event: WidgetEvent,
         input_state: InputState,
+        context: &mut EventContext,
         popups: Option<ChildPopupsMut<A, F>>,
         source_child: &[WidgetIdent]
     ) -> EventOps<A, F>;
@@ -225,6 +226,9 @@ pub trait Widget<A, F: RenderFrame> {
     fn size_bounds(&self) -> SizeBounds {
         SizeBounds::default()
     }
+    /// Recompute the widget's size bounds. Called before the widget's parent lays it out, so that
+    /// widgets can measure their contents with the theme before they're first rendered.
+    fn update_size_bounds(&mut self, _theme: &F::Theme) {}
     fn register_timers(&self, _register: &mut TimerRegister) {}
     fn accepts_focus(&self) -> OnFocus {
         OnFocus::default()
@@ -339,6 +343,11 @@ impl UpdateTag {
         self.last_root.set(self.last_root.get() & !UPDATE_TIMER);
     }
 
+    #[inline]
+    pub(crate) fn mark_render_all_immutable(&self) {
+        self.last_root.set(self.last_root.get() | RENDER_ALL);
+    }
+
     #[inline]
     pub(crate) fn mark_update_child_immutable(&self) {
         self.last_root.set(self.last_root.get() | UPDATE_CHILD);
@@ -0,0 +1,84 @@
+extern crate derin;
+
+use derin::{LoopFlow, Window, WindowAttributes};
+use derin::layout::{Margins, LayoutHorizontal};
+use derin::container::SingleContainer;
+use derin::gl_render::RenderString;
+use derin::widgets::{Canvas, DrawCommand, Group, Path, Stroke};
+use derin::theme::Fill;
+use derin::theme::color::{Rgba, Nu8};
+use derin::geometry::{BoundBox, Point2};
+
+fn main() {
+    let black = Rgba::new(Nu8(0), Nu8(0), Nu8(0), Nu8(255));
+    let blue = Rgba::new(Nu8(0), Nu8(102), Nu8(204), Nu8(255));
+
+    let mut wave = Path::new();
+    wave.move_to(Point2::new(16.0, 200.0));
+    wave.cubic_to(Point2::new(80.0, 120.0), Point2::new(144.0, 280.0), Point2::new(208.0, 200.0));
+    wave.quad_to(Point2::new(240.0, 160.0), Point2::new(272.0, 200.0));
+
+    let mut triangle = Path::new();
+    triangle.move_to(Point2::new(200.0, 24.0));
+    triangle.line_to(Point2::new(264.0, 120.0));
+    triangle.line_to(Point2::new(136.0, 120.0));
+    triangle.close();
+
+    let canvas = Canvas::new(vec![
+        DrawCommand::Rect {
+            rect: BoundBox::new2(16, 24, 112, 120),
+            fill: Fill::VerticalGradient {
+                top: Rgba::new(Nu8(255), Nu8(255), Nu8(255), Nu8(255)),
+                bottom: blue
+            },
+            stroke: Some(Stroke{ color: black, width: 2.0 }),
+            corner_radius: 8
+        },
+        DrawCommand::Circle {
+            center: Point2::new(64, 72),
+            radius: 24,
+            fill: Fill::Color(Rgba::new(Nu8(255), Nu8(192), Nu8(0), Nu8(255))),
+            stroke: None
+        },
+        DrawCommand::Path {
+            path: triangle,
+            fill: Some(blue),
+            stroke: Some(Stroke{ color: black, width: 3.0 })
+        },
+        DrawCommand::Path {
+            path: wave,
+            fill: None,
+            stroke: Some(Stroke{ color: blue, width: 4.0 })
+        },
+        DrawCommand::Line {
+            start: Point2::new(16.0, 248.0),
+            end: Point2::new(272.0, 248.0),
+            stroke: Stroke{ color: black, width: 1.0 }
+        },
+        DrawCommand::Text {
+            rect: BoundBox::new2(16, 256, 272, 280),
+            string: RenderString::new("Drawn on a canvas".to_string()),
+            theme_path: "Label".to_string()
+        }
+    ]);
+
+    let group = Group::new(
+        SingleContainer::new(canvas),
+        LayoutHorizontal::new(Margins::new(8, 8, 8, 8), Default::default())
+    );
+    let theme = derin::theme::Theme::default();
+
+    let window_attributes = WindowAttributes {
+        dimensions: Some((304, 304)),
+        title: "Canvas Example".to_string(),
+        ..WindowAttributes::default()
+    };
+
+    let mut window = unsafe{ Window::new(window_attributes, group, theme).unwrap() };
+    let _: Option<()> = window.run_forever(
+        |_: (), _, _| {
+            LoopFlow::Continue
+        },
+        |_, _| None
+    );
+}
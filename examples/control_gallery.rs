@@ -4,7 +4,7 @@ extern crate derin_macros;
 
 use derin::{LoopFlow, Window, WindowAttributes};
 use derin::layout::{Margins, LayoutHorizontal, LayoutVertical};
-use derin::widgets::{Contents, Button, EditBox, Group, Label};
+use derin::widgets::{Contents, Button, EditBox, Group, Label, SpinBox};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum GalleryEvent {
@@ -22,7 +22,8 @@ struct BasicContainer {
 #[derin(action = "GalleryEvent")]
 struct NestedContainer {
     label: Label,
-    edit_box: EditBox,
+    edit_box: EditBox<Option<GalleryEvent>>,
+    spin_box: SpinBox<f32, Option<GalleryEvent>>,
     #[derin(collection = "Button<Option<GalleryEvent>>")]
     buttons: Vec<Button<Option<GalleryEvent>>>
 }
@@ -34,7 +35,8 @@ fn main() {
             nested: Group::new(
                 NestedContainer {
                     label: Label::new(Contents::Text("Nested Container".to_string())),
-                    edit_box: EditBox::new("A Text Box".to_string()),
+                    edit_box: EditBox::new("A Text Box".to_string(), None),
+                    spin_box: SpinBox::new(0.0, None),
                     buttons: Vec::new(),
                 },
                 LayoutVertical::new(Margins::new(8, 8, 8, 8), Default::default())
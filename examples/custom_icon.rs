@@ -21,6 +21,7 @@ fn main() {
         "AddIcon".to_string(),
         ThemeWidget {
             text: None,
+            shape: None,
             image: Some(Rc::new(Image {
                 pixels: {
                     let image_png = png::Decoder::new(::std::io::Cursor::new(&include_bytes!("plus_icon.png")[..]));
@@ -26,7 +26,7 @@ use core::tree::{WidgetIdent, WidgetSummary, Widget};
 /// #[derin(action = "SimpleAction")]
 /// struct Container {
 ///     label: Label,
-///     edit_box: EditBox,
+///     edit_box: EditBox<Option<SimpleAction>>,
 ///     #[derin(collection = "Button<Option<GalleryEvent>>")]
 ///     buttons: Vec<Button<Option<GalleryEvent>>>
 /// }
@@ -1,15 +1,23 @@
-use std::cmp;
+use std::rc::{Rc, Weak};
 use std::collections::HashMap;
 
 use cgmath::{Point2, Vector2};
-use cgmath_geometry::{OffsetBox, DimsBox, GeoBox};
+use cgmath_geometry::{OffsetBox, DimsBox};
 
 use gullery::glsl::Nu8;
 use gullery::colors::Rgba;
 
-use dat::SkylineAtlas;
+use dat::{PagedAtlas, SkylineAtlas};
 
-use theme::ThemeFace;
+use theme::{Image, ThemeFace};
+
+/// The number of frames an entry can go unused before it gets evicted. Entries are only evicted
+/// when the atlas runs out of space.
+const EVICTION_AGE: u64 = 120;
+/// The number of pages the atlas can grow to before it starts evicting the least recently used
+/// entries, regardless of their age. Entries that may still be on screen never get evicted, so
+/// the atlas grows past this if they don't fit.
+const MAX_PAGES: usize = 4;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 struct GlyphKey {
@@ -18,78 +26,232 @@ struct GlyphKey {
     glyph_index: u32
 }
 
+/// A rect within one of the atlas's pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasRect {
+    pub page: usize,
+    pub rect: OffsetBox<Point2<u32>>
+}
+
+#[derive(Debug, Clone, Copy)]
+struct GlyphEntry {
+    rect: AtlasRect,
+    bearing: Vector2<i32>,
+    is_colored: bool,
+    last_used: u64
+}
+
+/// Identifies one of the atlas's entries while it's being repacked.
+enum EntryKey {
+    White,
+    Image(String),
+    Glyph(GlyphKey)
+}
+
+#[derive(Debug, Clone)]
+struct ImageEntry {
+    /// The image stored in `rect`. If the theme's image for the entry's path isn't this image, the
+    /// theme has changed and the entry is out-of-date.
+    image: Weak<Image>,
+    rect: AtlasRect,
+    last_used: u64
+}
+
 pub struct Atlas {
-    atlas: SkylineAtlas<Rgba<Nu8>>,
-    white_rect: Option<OffsetBox<Point2<u32>>>,
-    // image_rects: HashMap<(), OffsetBox<Point2<u32>>>,
-    glyph_rects: HashMap<GlyphKey, (OffsetBox<Point2<u32>>, Vector2<i32>)>,
-    // image_rects: hashmap,
-    // glyph_rects: hashmap
+    atlas: PagedAtlas<Rgba<Nu8>>,
+    white_rect: Option<AtlasRect>,
+    image_rects: HashMap<String, ImageEntry>,
+    glyph_rects: HashMap<GlyphKey, GlyphEntry>,
+    frame_count: u64,
+    /// The frame of the last full redraw. Entries used since then may still be drawn by retained
+    /// vertices.
+    visible_since: u64,
+    /// Whether the atlas had to add a page to fit a new entry this frame.
+    under_pressure: bool,
+    /// Whether each page's pixels have changed since they were last checked with `take_modified`.
+    modified_pages: Vec<bool>
 }
 
 impl Atlas {
     pub fn new() -> Atlas {
         Atlas {
-            atlas: SkylineAtlas::new(Rgba::new(Nu8(0), Nu8(0), Nu8(0), Nu8(0)), DimsBox::new2(1024, 1024)),
+            atlas: Atlas::new_paged_atlas(),
             white_rect: None,
-            // image_rects: HashMap::new(),
-            glyph_rects: HashMap::new()
+            image_rects: HashMap::new(),
+            glyph_rects: HashMap::new(),
+            frame_count: 0,
+            visible_since: 0,
+            under_pressure: false,
+            modified_pages: vec![true]
         }
     }
 
-    pub fn dims(&self) -> DimsBox<Point2<u32>> {
-        self.atlas.dims()
+    fn new_paged_atlas() -> PagedAtlas<Rgba<Nu8>> {
+        PagedAtlas::new(Rgba::new(Nu8(0), Nu8(0), Nu8(0), Nu8(0)), DimsBox::new2(1024, 1024))
+    }
+
+    pub fn pages(&self) -> &[SkylineAtlas<Rgba<Nu8>>] {
+        self.atlas.pages()
     }
 
-    pub fn pixels(&self) -> &[Rgba<Nu8>] {
-        self.atlas.pixels()
+    /// Returns `true` if the page's pixels have changed since the last call to this function, and
+    /// need to be re-uploaded.
+    pub fn take_modified(&mut self, page: usize) -> bool {
+        match self.modified_pages.get_mut(page) {
+            Some(modified) => {
+                let was_modified = *modified;
+                *modified = false;
+                was_modified
+            },
+            None => false
+        }
     }
 
     /// Tell the atlas that a new frame has begun. This can be used to tell how old an image is, and
-    /// to throw away pixel data that's been unused for a while.
-    pub fn bump_frame_count(&mut self) {
-        self.atlas.clear(None);
-        self.white_rect = None;
-        // self.image_rects.clear();
-        self.glyph_rects.clear();
+    /// to throw away pixel data that's been unused for a while. `full_redraw` is whether every
+    /// entry drawn last frame was retrieved during it, rather than being drawn by retained vertices.
+    ///
+    /// If the atlas ran out of space during the last frame, entries that haven't been used in a
+    /// while get evicted and the remaining entries get compacted. Returns `true` if that happened,
+    /// in which case rects retrieved before calling this are invalidated.
+    pub fn bump_frame_count(&mut self, full_redraw: bool) -> bool {
+        if full_redraw {
+            self.visible_since = self.frame_count;
+        }
+
+        let compacted = self.under_pressure;
+        if self.under_pressure {
+            self.evict_and_compact();
+            self.under_pressure = false;
+        }
+        self.frame_count += 1;
+        compacted
     }
 
-    pub fn white(&mut self) -> OffsetBox<Point2<u32>> {
-        let white_pic = (
-            &[Rgba::new(Nu8(255), Nu8(255), Nu8(255), Nu8(255))][..],
-            DimsBox::new2(1, 1)
+    fn evict_and_compact(&mut self) {
+        let Atlas {
+            ref mut atlas,
+            ref mut white_rect,
+            ref mut image_rects,
+            ref mut glyph_rects,
+            frame_count,
+            visible_since,
+            ..
+        } = *self;
+
+        // Entries used since the last full redraw may be drawn by retained vertices, so they have
+        // to stay where they are until the next full redraw.
+        let is_evictable = |last_used: u64| last_used < visible_since;
+        let is_stale = |last_used: u64| is_evictable(last_used) && last_used + EVICTION_AGE < frame_count;
+        image_rects.retain(|_, entry| !is_stale(entry.last_used));
+        glyph_rects.retain(|_, entry| !is_stale(entry.last_used));
+
+        // Rects can't be moved between pages by compacting them, so the remaining entries get
+        // repacked into a new atlas, most recently used first. Any evictable entries that don't fit
+        // within `MAX_PAGES` get evicted.
+        let mut entries: Vec<(u64, EntryKey)> =
+            white_rect.iter().map(|_| (u64::max_value(), EntryKey::White))
+                .chain(image_rects.iter().map(|(path, entry)| (entry.last_used, EntryKey::Image(path.clone()))))
+                .chain(glyph_rects.iter().map(|(key, entry)| (entry.last_used, EntryKey::Glyph(key.clone()))))
+                .collect();
+        entries.sort_by(|&(last_used_a, _), &(last_used_b, _)| last_used_b.cmp(&last_used_a));
+
+        let mut repacked = Atlas::new_paged_atlas();
+        repacked.set_heuristic(atlas.heuristic());
+        for (last_used, key) in entries {
+            let entry_rect = match key {
+                EntryKey::White => white_rect.as_mut().unwrap(),
+                EntryKey::Image(ref path) => &mut image_rects.get_mut(path).unwrap().rect,
+                EntryKey::Glyph(ref glyph) => &mut glyph_rects.get_mut(glyph).unwrap().rect
+            };
+            let old_page = &atlas.pages()[entry_rect.page];
+            let (page, rect) = repacked.add_image(old_page.dims(), entry_rect.rect, old_page.pixels());
+
+            if page >= MAX_PAGES && is_evictable(last_used) {
+                repacked.remove_image(page, rect);
+                match key {
+                    EntryKey::White => (),
+                    EntryKey::Image(path) => {image_rects.remove(&path);},
+                    EntryKey::Glyph(glyph) => {glyph_rects.remove(&glyph);}
+                }
+            } else {
+                *entry_rect = AtlasRect{ page, rect };
+            }
+        }
+
+        // Evicting entries can leave empty pages at the end of the atlas, which get removed here.
+        repacked.compact(
+            white_rect.iter_mut()
+                .chain(image_rects.values_mut().map(|entry| &mut entry.rect))
+                .chain(glyph_rects.values_mut().map(|entry| &mut entry.rect))
+                .map(|rect| (rect.page, &mut rect.rect))
         );
-        self.white_rect.unwrap_or_else(|| self.image_rect("TODO: REPLACE WHEN STRINGS MATTER", || white_pic))
+        *atlas = repacked;
+        self.modified_pages = vec![true; self.atlas.pages().len()];
     }
 
-    /// Retrieve an image from the atlas. `image_path` refers to the theme's name for the image,
-    /// while `get_image` is used to add the image to the atlas in case it's not already stored.
-    pub fn image_rect<'a, F>(&mut self, _image_path: &str, get_image: F) -> OffsetBox<Point2<u32>>
-        where F: FnOnce() -> (&'a [Rgba<Nu8>], DimsBox<Point2<u32>>)
-    {
-        let (pixels, dims) = get_image();
-        match self.atlas.add_image(dims, dims.into(), pixels) {
-            Some(rect) => rect,
-            None => {
-                let new_width = cmp::max(dims.width(), self.atlas.dims().width());
-                let new_height = self.atlas.dims().height() + cmp::max(self.atlas.dims().height(), dims.height());
-                self.atlas.set_dims(
-                    Rgba::new(Nu8(0), Nu8(0), Nu8(0), Nu8(0)),
-                    DimsBox::new2(new_width, new_height)
-                );
-
-                self.atlas.add_image(dims, dims.into(), pixels).unwrap()
+    /// Record that a rect was added to the atlas.
+    fn mark_added(modified_pages: &mut Vec<bool>, under_pressure: &mut bool, num_pages: usize, page: usize) {
+        if modified_pages.len() < num_pages {
+            *under_pressure = true;
+            modified_pages.resize(num_pages, true);
+        }
+        modified_pages[page] = true;
+    }
+
+    pub fn white(&mut self) -> AtlasRect {
+        if let Some(rect) = self.white_rect {
+            return rect;
+        }
+
+        let white_pic = [Rgba::new(Nu8(255), Nu8(255), Nu8(255), Nu8(255))];
+        let rect = self.add_image(&white_pic, DimsBox::new2(1, 1));
+        self.white_rect = Some(rect);
+        rect
+    }
+
+    /// Retrieve an image from the atlas. `image_path` refers to the theme's name for the image, and
+    /// `image` is the theme's image for that path. `image` gets added to the atlas if the atlas
+    /// doesn't already store it under `image_path`, or if it stores a different image there.
+    pub fn image_rect(&mut self, image_path: &str, image: &Rc<Image>) -> AtlasRect {
+        let frame_count = self.frame_count;
+        if let Some(entry) = self.image_rects.get_mut(image_path) {
+            let is_same_image = entry.image.upgrade().map(|i| Rc::ptr_eq(&i, image)).unwrap_or(false);
+            if is_same_image {
+                entry.last_used = frame_count;
+                return entry.rect;
             }
+
+            // The theme's image has changed. The old image's space isn't freed here, since retained
+            // vertices may still be sampling from it and the space could get reused by the next
+            // image added. Replacing the entry drops the old rect, so the space gets reclaimed the
+            // next time the atlas gets compacted, which invalidates all retained vertices anyway.
         }
+
+        let rect = self.add_image(&image.pixels, image.dims);
+        self.image_rects.insert(image_path.to_string(), ImageEntry {
+            image: Rc::downgrade(image),
+            rect,
+            last_used: frame_count
+        });
+        rect
+    }
+
+    fn add_image(&mut self, pixels: &[Rgba<Nu8>], dims: DimsBox<Point2<u32>>) -> AtlasRect {
+        let (page, rect) = self.atlas.add_image(dims, dims.into(), pixels);
+        Atlas::mark_added(&mut self.modified_pages, &mut self.under_pressure, self.atlas.pages().len(), page);
+        AtlasRect{ page, rect }
     }
 
     /// Retrieve a glyph and it's bearing from the atlas. `style` and `glyph_index` are used as keys for
     /// the glyph, while `get_glyph` is used to add the glyph to the atlas in case it's not already stored
     /// within the atlas.
     ///
-    /// `get_glyph` returns `(pixel_buf, image_dims, glyph_bearing)`
-    pub fn glyph_rect<'a, F, I, J>(&mut self, face: ThemeFace, face_size: u32, glyph_index: u32, get_glyph: F) -> (OffsetBox<Point2<u32>>, Vector2<i32>)
-        where F: FnOnce() -> (I, DimsBox<Point2<u32>>, Vector2<i32>),
+    /// `get_glyph` returns `(pixel_buf, image_dims, glyph_bearing, is_colored)`, where `is_colored`
+    /// is `true` if the glyph has its own colors (such as a color emoji) and shouldn't be tinted
+    /// with the text color.
+    pub fn glyph_rect<'a, F, I, J>(&mut self, face: ThemeFace, face_size: u32, glyph_index: u32, get_glyph: F) -> (AtlasRect, Vector2<i32>, bool)
+        where F: FnOnce() -> (I, DimsBox<Point2<u32>>, Vector2<i32>, bool),
               I: 'a + IntoIterator<Item=J>,
               J: 'a + IntoIterator<Item=Rgba<Nu8>>
     {
@@ -102,23 +264,18 @@ impl Atlas {
         let Atlas {
             ref mut glyph_rects,
             ref mut atlas,
+            ref mut under_pressure,
+            ref mut modified_pages,
+            frame_count,
             ..
         } = *self;
-        *glyph_rects.entry(key).or_insert_with(|| {
-            let (pixels, dims, bearing) = get_glyph();
-            match atlas.add_image_pixels(dims, pixels) {
-                Ok(rect) => (rect, bearing),
-                Err(pixels) => {
-                    let new_width = cmp::max(dims.width(), atlas.dims().width());
-                    let new_height = atlas.dims().height() + cmp::max(atlas.dims().height(), dims.height());
-                    atlas.set_dims(
-                        Rgba::new(Nu8(0), Nu8(0), Nu8(0), Nu8(0)),
-                        DimsBox::new2(new_width, new_height)
-                    );
-
-                    (atlas.add_image_pixels(dims, pixels).unwrap_or_else(|_| panic!("bad resize")), bearing)
-                }
-            }
-        })
+        let entry = glyph_rects.entry(key).or_insert_with(|| {
+            let (pixels, dims, bearing, is_colored) = get_glyph();
+            let (page, rect) = atlas.add_image_pixels(dims, pixels);
+            Atlas::mark_added(modified_pages, under_pressure, atlas.pages().len(), page);
+            GlyphEntry{ rect: AtlasRect{ page, rect }, bearing, is_colored, last_used: frame_count }
+        });
+        entry.last_used = frame_count;
+        (entry.rect, entry.bearing, entry.is_colored)
     }
 }
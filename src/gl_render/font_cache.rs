@@ -57,4 +57,14 @@ impl FontCache {
             }
         }
     }
+
+    /// Find the first face in `faces` that has a glyph for `c`. Faces that fail to load are skipped.
+    pub fn face_for_char<'a, I>(&mut self, faces: I, c: char) -> Option<&'a ThemeFace>
+        where I: IntoIterator<Item=&'a ThemeFace>
+    {
+        faces.into_iter().find(|theme_face| match self.face((*theme_face).clone()) {
+            Ok(face) => face.char_index(c) != 0,
+            Err(_) => false
+        })
+    }
 }
@@ -4,9 +4,12 @@
 
 mod atlas;
 mod font_cache;
+mod text_metrics;
 mod translate;
 
+use std::{cmp, mem};
 use std::rc::Rc;
+use std::ops::Range;
 use dct::cursor::CursorIcon;
 use dct::layout::SizeBounds;
 
@@ -20,7 +23,7 @@ use gullery::textures::targets::SimpleTex;
 use gullery::framebuffer::{DrawMode, Framebuffer, DefaultFramebuffer};
 use gullery::buffers::{Buffer, BufferUsage};
 use gullery::vao::VertexArrayObj;
-use gullery::glsl::Nu8;
+use gullery::glsl::{Nu8, TypeGroup};
 use gullery::colors::Rgba;
 
 use glyphydog::DPI;
@@ -30,13 +33,14 @@ use cgmath_geometry::{BoundBox, OffsetBox, DimsBox, GeoBox};
 use glutin::*;
 
 use theme::Theme;
-pub use core::render::{Renderer, RenderFrame};
+pub use core::render::{Renderer, RenderFrame, RenderFramePrimitive};
 use core::tree::WidgetIdent;
 
 use self::atlas::Atlas;
 use self::font_cache::FontCache;
 use self::translate::Translator;
-pub use self::translate::{EditString, Prim, ThemedPrim, RelPoint, RenderString};
+pub use self::text_metrics::TextMetrics;
+pub use self::translate::{DrawCommand, EditString, Path, PathSegment, Prim, ThemedPrim, RelPoint, RenderString, Stroke, TextSpan};
 
 pub struct GLRenderer {
     window: GlWindow,
@@ -50,26 +54,101 @@ pub struct GLFrame {
 }
 
 struct FrameDraw {
-    vertices: Vec<GLVertex>,
+    /// The vertices being translated for the current widget.
+    vertices: PagedVertices,
+    /// Scratch space that the undrawn widgets' batches get merged into before drawing.
+    merged_vertices: PagedVertices,
+    /// The vertices uploaded by each widget this frame, in draw order.
+    widget_vertices: Vec<WidgetVertices>,
+    /// The vertices uploaded by each widget last frame. Widgets that aren't re-rendered this frame
+    /// have their vertices moved from here into `widget_vertices`.
+    last_widget_vertices: Vec<WidgetVertices>,
+    /// The index in `last_widget_vertices` at which to start searching for retained vertices.
+    /// Widgets are visited in the same order every frame, so this is usually where they are.
+    retain_cursor: usize,
+    /// The number of `widget_vertices` that have already been drawn this frame.
+    drawn_widgets: usize,
+    /// Whether the retained vertices can't be reused next frame, and every widget has to be
+    /// re-translated.
+    needs_full_redraw: bool,
+    /// The generation of the theme the last frame was drawn with.
+    theme_generation: Option<usize>,
+    /// Whether every widget gets re-translated this frame, in which case the whole window gets
+    /// reported as damaged. The widget rects don't cover the window's background, or the rects
+    /// of widgets whose retained vertices were thrown away.
+    damage_window: bool,
+    /// The rects that changed in the last finished frame.
+    damaged_region: Vec<BoundBox<Point2<i32>>>,
     atlas: Atlas,
     font_cache: FontCache,
 
     // OpenGL structs
     context_state: Rc<ContextState>,
-    gl_tex_atlas: Texture<Rgba<Nu8>, SimpleTex<DimsBox<Point2<u32>>>>,
+    /// One texture for each of the atlas's pages.
+    gl_tex_pages: Vec<Texture<Rgba<Nu8>, SimpleTex<DimsBox<Point2<u32>>>>>,
     render_state: RenderState,
     fb: DefaultFramebuffer,
     program: Program<GLVertex, GLUniforms<'static>>,
     vao: VertexArrayObj<GLVertex, ()>,
+    shape_program: Program<ShapeVertex, ShapeUniforms>,
+    shape_vao: VertexArrayObj<ShapeVertex, ()>,
 }
 
 #[derive(TypeGroup, Debug, Clone, Copy)]
 struct GLVertex {
-    loc: Point2<i32>,
+    /// The vertex's position, in pixels. Fractional positions let canvas paths avoid getting
+    /// snapped to the pixel grid.
+    loc: Point2<f32>,
     color: Rgba<Nu8>,
     tex_coord: Point2<f32>
 }
 
+/// A vertex of a vector shape. Shapes get drawn with their own program, so that images and text
+/// don't have to carry the shape attributes.
+#[derive(TypeGroup, Debug, Clone, Copy)]
+struct ShapeVertex {
+    loc: Point2<f32>,
+    color: Rgba<Nu8>,
+    /// The vertex's position relative to the center of the shape being drawn.
+    shape_pos: Point2<f32>,
+    /// Half of the shape's width and height.
+    shape_half_dims: Vector2<f32>,
+    /// The shape's corner radius and border width.
+    shape_radius_border: Vector2<f32>,
+    border_color: Rgba<Nu8>
+}
+
+/// The vertices uploaded by a single widget.
+#[derive(Debug)]
+struct WidgetVertices {
+    ident: Vec<WidgetIdent>,
+    /// The area the widget uploaded its primitives in.
+    rect: BoundBox<Point2<i32>>,
+    vertices: PagedVertices
+}
+
+/// Vertices, split into batches that can each be drawn with a single draw call.
+#[derive(Debug, Default)]
+struct PagedVertices {
+    vertices: Vec<GLVertex>,
+    shape_vertices: Vec<ShapeVertex>,
+    /// The kind of each batch, and the range of its vertices in `vertices` or `shape_vertices`.
+    batches: Vec<(Batch, Range<usize>)>
+}
+
+/// What a batch of vertices gets drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Batch {
+    /// Textured vertices that sample from the given atlas page.
+    Page(usize),
+    Shapes
+}
+
+enum BatchVertices<'a> {
+    Page(usize, &'a [GLVertex]),
+    Shapes(&'a [ShapeVertex])
+}
+
 #[derive(Uniforms, Clone, Copy)]
 struct GLUniforms<'a> {
     atlas_size: Vector2<u32>,
@@ -77,7 +156,12 @@ struct GLUniforms<'a> {
     tex_atlas: &'a Texture<Rgba<Nu8>, SimpleTex<DimsBox<Point2<u32>>>>
 }
 
-pub trait PrimFrame: RenderFrame<Primitive=ThemedPrim<<Self as PrimFrame>::DirectRender>, Theme=Theme> {
+#[derive(Uniforms, Clone, Copy)]
+struct ShapeUniforms {
+    window_size: Point2<u32>
+}
+
+pub trait PrimFrame: RenderFrame<Theme=Theme> + for<'a> RenderFramePrimitive<'a, Primitive=ThemedPrim<'a, <Self as PrimFrame>::DirectRender>> {
     type DirectRender;
 }
 
@@ -102,17 +186,29 @@ impl GLRenderer {
 
         let program = Program::new(&vert_shader, None, &frag_shader).unwrap_werr();
 
-        let gl_tex_atlas = Texture::new(DimsBox::new2(1024, 1024), 1, context_state.clone()).unwrap();
+        let shape_vert_shader = Shader::new(SHAPE_VERT_SHADER, context_state.clone()).unwrap();
+        let shape_frag_shader = Shader::new(SHAPE_FRAG_SHADER, context_state.clone()).unwrap();
+        let shape_program = Program::new(&shape_vert_shader, None, &shape_frag_shader).unwrap_werr();
 
         Ok(GLRenderer {
             frame: GLFrame {
                 poly_translator: Translator::new(),
                 draw: FrameDraw {
-                    vertices: Vec::new(),
+                    vertices: PagedVertices::default(),
+                    merged_vertices: PagedVertices::default(),
+                    widget_vertices: Vec::new(),
+                    last_widget_vertices: Vec::new(),
+                    retain_cursor: 0,
+                    drawn_widgets: 0,
+                    needs_full_redraw: false,
+                    theme_generation: None,
+                    damage_window: true,
+                    damaged_region: Vec::new(),
                     atlas: Atlas::new(),
                     font_cache: FontCache::new(),
                     fb: DefaultFramebuffer::new(context_state.clone()),
                     vao: VertexArrayObj::new_noindex(Buffer::with_size(BufferUsage::StreamDraw, 2048 * 3, context_state.clone())),
+                    shape_vao: VertexArrayObj::new_noindex(Buffer::with_size(BufferUsage::StreamDraw, 256 * 6, context_state.clone())),
                     render_state: RenderState {
                         blend: Some(BlendFuncs {
                             src_rgb: BlendFunc::SrcAlpha,
@@ -123,7 +219,8 @@ impl GLRenderer {
                         ..RenderState::default()
                     },
                     program,
-                    gl_tex_atlas,
+                    shape_program,
+                    gl_tex_pages: Vec::new(),
                     context_state
                 }
             },
@@ -149,11 +246,32 @@ impl GLRenderer {
 
 impl Renderer for GLRenderer {
     type Frame = GLFrame;
-    fn force_full_redraw(&self) -> bool {true}
+    fn force_full_redraw(&self) -> bool {
+        self.frame.draw.needs_full_redraw
+    }
+
+    fn theme_changed(&mut self, theme: &Theme) -> bool {
+        let draw = &mut self.frame.draw;
+        let generation = Some(theme.generation());
+        if draw.theme_generation == generation {
+            return false;
+        }
+
+        // The retained vertices were translated with the old theme, so none of them can be reused.
+        draw.theme_generation = generation;
+        draw.damage_window = true;
+        draw.last_widget_vertices.clear();
+        draw.retain_cursor = 0;
+        true
+    }
 
     fn set_cursor_pos(&mut self, pos: Point2<i32>) {
         self.window.set_cursor_position(pos.x, pos.y).ok();
     }
+    fn set_ime_caret_rect(&mut self, rect: BoundBox<Point2<i32>>) {
+        // Place the candidate window below the caret, so it doesn't cover the text being composed.
+        self.window.set_ime_spot(rect.min.x, rect.max.y);
+    }
     fn set_cursor_icon(&mut self, icon: CursorIcon) {
         let glutin_icon = match icon {
             CursorIcon::Pointer => MouseCursor::Default,
@@ -198,39 +316,307 @@ impl Renderer for GLRenderer {
         }
     }
 
+    fn damaged_region(&self) -> Option<&[BoundBox<Point2<i32>>]> {
+        Some(&self.frame.draw.damaged_region)
+    }
+
     fn make_frame(&mut self) -> (&mut GLFrame, BoundBox<Point2<i32>>) {
         let (width, height) = self.window.get_inner_size().unwrap();
-        self.frame.draw.render_state.viewport = DimsBox::new2(width, height).into();
+        let draw = &mut self.frame.draw;
+        let viewport: OffsetBox<Point2<u32>> = DimsBox::new2(width, height).into();
+        if draw.needs_full_redraw || draw.render_state.viewport != viewport {
+            draw.damage_window = true;
+        }
+        draw.render_state.viewport = viewport;
+        draw.needs_full_redraw = false;
+        draw.damaged_region.clear();
 
         (&mut self.frame, BoundBox::new2(0, 0, width as i32, height as i32))
     }
 
     fn finish_frame(&mut self, _: &Theme) {
-        self.frame.draw.draw_contents();
+        let draw = &mut self.frame.draw;
+        draw.draw_contents();
         self.window.swap_buffers().unwrap();
-        self.frame.draw.atlas.bump_frame_count();
+
+        draw.update_damaged_region();
+        // Retained vertices get moved out of `last_widget_vertices`, so if none of them are empty
+        // every widget was translated this frame.
+        let full_redraw = draw.last_widget_vertices.iter().all(|w| w.vertices.len() != 0);
+        mem::swap(&mut draw.widget_vertices, &mut draw.last_widget_vertices);
+        draw.widget_vertices.clear();
+        draw.retain_cursor = 0;
+        draw.drawn_widgets = 0;
+
+        // Compacting the atlas moves its images, which invalidates the retained vertices' texture
+        // coordinates.
+        if draw.atlas.bump_frame_count(full_redraw) {
+            draw.needs_full_redraw = true;
+        }
     }
 }
 
 impl FrameDraw {
+    /// Draw all the vertices that haven't been drawn yet this frame.
+    ///
+    /// The current widget's vertices get drawn and thrown away, rather than being retained.
     fn draw_contents(&mut self) {
-        let atlas_dims = self.atlas.dims();
-        if atlas_dims != self.gl_tex_atlas.dims() {
-            self.gl_tex_atlas = Texture::new(atlas_dims, 1, self.context_state.clone()).unwrap();
+        self.upload_atlas_pages();
+
+        let window_size = Point2::from_vec(self.render_state.viewport.dims());
+        let FrameDraw {
+            ref mut vertices,
+            ref mut merged_vertices,
+            ref widget_vertices,
+            ref mut drawn_widgets,
+            ref gl_tex_pages,
+            ref mut fb,
+            ref program,
+            ref mut vao,
+            ref shape_program,
+            ref mut shape_vao,
+            ref context_state,
+            render_state,
+            ..
+        } = *self;
+
+        // Neighboring widgets usually draw from the same atlas page, so merging their batches lets
+        // each run of them get uploaded and drawn in one go.
+        merged_vertices.clear();
+        for undrawn_vertices in widget_vertices[*drawn_widgets..].iter().map(|w| &w.vertices).chain(Some(&*vertices)) {
+            merged_vertices.extend_from(undrawn_vertices);
         }
-        self.gl_tex_atlas.sub_image(0, Vector2::new(0, 0), atlas_dims, self.atlas.pixels());
 
-        let uniform = GLUniforms {
-            atlas_size: self.gl_tex_atlas.dims().dims,
-            window_size: Point2::from_vec(self.render_state.viewport.dims()),
-            tex_atlas: &self.gl_tex_atlas
-        };
+        for batch in merged_vertices.batches() {
+            match batch {
+                BatchVertices::Page(page, verts) => {
+                    let uniform = GLUniforms {
+                        atlas_size: gl_tex_pages[page].dims().dims,
+                        window_size,
+                        tex_atlas: &gl_tex_pages[page]
+                    };
+                    upload_vertices(vao, verts, context_state);
+                    fb.draw(DrawMode::Triangles, 0..verts.len(), vao, program, uniform, render_state);
+                },
+                BatchVertices::Shapes(verts) => {
+                    upload_vertices(shape_vao, verts, context_state);
+                    fb.draw(DrawMode::Triangles, 0..verts.len(), shape_vao, shape_program, ShapeUniforms{ window_size }, render_state);
+                }
+            }
+        }
+
+        *drawn_widgets = widget_vertices.len();
+        vertices.clear();
+    }
+
+    /// Move the current widget's vertices into `widget_vertices`, so they can be retained.
+    fn store_widget_vertices(&mut self, ident: &[WidgetIdent], rect: BoundBox<Point2<i32>>) {
+        let vertices = mem::replace(&mut self.vertices, PagedVertices::default());
+        self.push_widget_vertices(ident, rect, vertices, true);
+    }
+
+    fn push_widget_vertices(&mut self, ident: &[WidgetIdent], rect: BoundBox<Point2<i32>>, vertices: PagedVertices, damaged: bool) {
+        if vertices.len() == 0 {
+            return;
+        }
+        if damaged {
+            add_damage(&mut self.damaged_region, rect);
+        }
+
+        // Widgets can upload primitives several times while rendering, so append to the widget's
+        // vertices if it's already uploaded some this frame.
+        let last_is_drawn = self.drawn_widgets == self.widget_vertices.len();
+        match self.widget_vertices.last_mut() {
+            Some(last) if last.ident == ident && !last_is_drawn => {
+                last.rect = BoundBox::new2(
+                    cmp::min(last.rect.min.x, rect.min.x), cmp::min(last.rect.min.y, rect.min.y),
+                    cmp::max(last.rect.max.x, rect.max.x), cmp::max(last.rect.max.y, rect.max.y)
+                );
+                last.vertices.extend_from(&vertices);
+            },
+            _ => self.widget_vertices.push(WidgetVertices {
+                ident: ident.to_vec(),
+                rect,
+                vertices
+            })
+        }
+    }
+
+    /// Add the rects of last frame's widgets that weren't retained to the damaged region, since
+    /// they were either re-rendered or removed. Must be called before `last_widget_vertices` gets
+    /// replaced with this frame's vertices.
+    ///
+    /// `damaged_region` already contains the rects of the widgets uploaded this frame.
+    fn update_damaged_region(&mut self) {
+        // Directly-rendered contents aren't uploaded as vertices, so drawing them damages the
+        // whole window too.
+        if self.damage_window || self.needs_full_redraw {
+            let window_dims = self.render_state.viewport.dims();
+            self.damaged_region.clear();
+            self.damaged_region.push(BoundBox::new2(0, 0, window_dims.x as i32, window_dims.y as i32));
+            self.damage_window = false;
+            return;
+        }
+
+        // Retained vertices get moved out of `last_widget_vertices`, and only non-empty vertices
+        // are ever stored, so the empty entries are the retained ones.
+        for unretained in self.last_widget_vertices.iter().filter(|w| w.vertices.len() != 0) {
+            add_damage(&mut self.damaged_region, unretained.rect);
+        }
+    }
+
+    /// Find the first of last frame's vertices that match `predicate`, starting at the retain
+    /// cursor.
+    fn find_retained<P: Fn(&[WidgetIdent]) -> bool>(&self, predicate: P) -> Option<usize> {
+        let retain_cursor = cmp::min(self.retain_cursor, self.last_widget_vertices.len());
+        let (before_cursor, after_cursor) = self.last_widget_vertices.split_at(retain_cursor);
+        after_cursor.iter().position(|w| predicate(&w.ident)).map(|i| i + retain_cursor)
+            .or_else(|| before_cursor.iter().position(|w| predicate(&w.ident)))
+    }
+
+    fn retain_vertices(&mut self, ident: &[WidgetIdent]) {
+        if let Some(index) = self.find_retained(|w| w == ident) {
+            let rect = self.last_widget_vertices[index].rect;
+            let vertices = mem::replace(&mut self.last_widget_vertices[index].vertices, PagedVertices::default());
+            self.push_widget_vertices(ident, rect, vertices, false);
+            self.retain_cursor = index + 1;
+        }
+    }
+
+    fn retain_descendant_vertices(&mut self, ident: &[WidgetIdent]) {
+        let is_descendant = |w: &[WidgetIdent]| w.len() > ident.len() && w.starts_with(ident);
+        if let Some(first_index) = self.find_retained(&is_descendant) {
+            // Widgets are visited depth-first, so a widget's descendants are always next to
+            // each other.
+            let mut index = first_index;
+            while index < self.last_widget_vertices.len() && is_descendant(&self.last_widget_vertices[index].ident) {
+                let (widget_ident, rect, vertices) = {
+                    let retained = &mut self.last_widget_vertices[index];
+                    (retained.ident.clone(), retained.rect, mem::replace(&mut retained.vertices, PagedVertices::default()))
+                };
+                self.push_widget_vertices(&widget_ident, rect, vertices, false);
+                index += 1;
+            }
+            self.retain_cursor = index;
+        }
+    }
+
+    /// Make sure there's an up-to-date texture for each of the atlas's pages.
+    fn upload_atlas_pages(&mut self) {
+        let num_pages = self.atlas.pages().len();
+        self.gl_tex_pages.truncate(num_pages);
+
+        for page_index in 0..num_pages {
+            let page_dims = self.atlas.pages()[page_index].dims();
+            let mut page_modified = self.atlas.take_modified(page_index);
+
+            let needs_new_texture = self.gl_tex_pages.get(page_index).map(|t| t.dims() != page_dims).unwrap_or(true);
+            if needs_new_texture {
+                let texture = Texture::new(page_dims, 1, self.context_state.clone()).unwrap();
+                match page_index < self.gl_tex_pages.len() {
+                    true => self.gl_tex_pages[page_index] = texture,
+                    false => self.gl_tex_pages.push(texture)
+                }
+                page_modified = true;
+            }
+
+            if page_modified {
+                self.gl_tex_pages[page_index].sub_image(0, Vector2::new(0, 0), page_dims, self.atlas.pages()[page_index].pixels());
+            }
+        }
+    }
+}
+
+/// Upload vertices to the start of a VAO's vertex buffer, replacing the buffer with a bigger one if
+/// they don't fit.
+fn upload_vertices<V: TypeGroup>(vao: &mut VertexArrayObj<V, ()>, vertices: &[V], context_state: &Rc<ContextState>) {
+    if vao.vertex_buffer().size() < vertices.len() {
+        let size = vertices.len().next_power_of_two();
+        *vao = VertexArrayObj::new_noindex(Buffer::with_size(BufferUsage::StreamDraw, size, context_state.clone()));
+    }
+    vao.vertex_buffer_mut().sub_data(0, vertices);
+}
+
+/// Add a rect to a damaged region, skipping it if it's already covered and dropping any rects it
+/// covers.
+fn add_damage(damaged_region: &mut Vec<BoundBox<Point2<i32>>>, rect: BoundBox<Point2<i32>>) {
+    let contains = |outer: &BoundBox<Point2<i32>>, inner: &BoundBox<Point2<i32>>|
+        outer.min.x <= inner.min.x && outer.min.y <= inner.min.y &&
+        inner.max.x <= outer.max.x && inner.max.y <= outer.max.y;
+
+    if damaged_region.iter().any(|damaged| contains(damaged, &rect)) {
+        return;
+    }
+    damaged_region.retain(|damaged| !contains(&rect, damaged));
+    damaged_region.push(rect);
+}
+
+impl PagedVertices {
+    fn batches<'a>(&'a self) -> impl 'a + Iterator<Item=BatchVertices<'a>> {
+        self.batches.iter().map(move |&(batch, ref range)| match batch {
+            Batch::Page(page) => BatchVertices::Page(page, &self.vertices[range.clone()]),
+            Batch::Shapes => BatchVertices::Shapes(&self.shape_vertices[range.clone()])
+        })
+    }
+
+    fn len(&self) -> usize {
+        self.vertices.len() + self.shape_vertices.len()
+    }
 
-        for verts in self.vertices.chunks(self.vao.vertex_buffer().size()) {
-            self.vao.vertex_buffer_mut().sub_data(0, verts);
-            self.fb.draw(DrawMode::Triangles, 0..verts.len(), &self.vao, &self.program, uniform, self.render_state);
+    fn extend_from(&mut self, other: &PagedVertices) {
+        for batch in other.batches() {
+            match batch {
+                BatchVertices::Page(page, verts) => self.extend(verts.iter().map(|&v| (page, v))),
+                BatchVertices::Shapes(verts) => self.extend(verts.iter().cloned())
+            }
         }
+    }
+
+    /// Get the batch that new vertices of the given kind get added to, starting a new one if the
+    /// last batch is of a different kind.
+    fn batch_mut(&mut self, batch: Batch) -> &mut Range<usize> {
+        if self.batches.last().map(|&(last_batch, _)| last_batch) != Some(batch) {
+            let start = match batch {
+                Batch::Page(_) => self.vertices.len(),
+                Batch::Shapes => self.shape_vertices.len()
+            };
+            self.batches.push((batch, start..start));
+        }
+        &mut self.batches.last_mut().unwrap().1
+    }
+
+    fn clear(&mut self) {
         self.vertices.clear();
+        self.shape_vertices.clear();
+        self.batches.clear();
+    }
+}
+
+impl Extend<(usize, GLVertex)> for PagedVertices {
+    fn extend<I: IntoIterator<Item=(usize, GLVertex)>>(&mut self, iter: I) {
+        for (page, vertex) in iter {
+            self.batch_mut(Batch::Page(page)).end += 1;
+            self.vertices.push(vertex);
+        }
+    }
+}
+
+impl Extend<ShapeVertex> for PagedVertices {
+    fn extend<I: IntoIterator<Item=ShapeVertex>>(&mut self, iter: I) {
+        for vertex in iter {
+            self.batch_mut(Batch::Shapes).end += 1;
+            self.shape_vertices.push(vertex);
+        }
+    }
+}
+
+impl GLVertex {
+    #[inline]
+    fn new(loc: Point2<i32>, color: Rgba<Nu8>, tex_coord: Point2<f32>) -> GLVertex {
+        GLVertex {
+            loc: Point2::new(loc.x as f32, loc.y as f32),
+            color, tex_coord
+        }
     }
 }
 
@@ -238,13 +624,16 @@ impl PrimFrame for GLFrame {
     type DirectRender = (DefaultFramebuffer, OffsetBox<Point2<u32>>, Rc<ContextState>);
 }
 
+impl<'a> RenderFramePrimitive<'a> for GLFrame {
+    type Primitive = ThemedPrim<'a, <Self as PrimFrame>::DirectRender>;
+}
+
 impl RenderFrame for GLFrame {
     type Transform = BoundBox<Point2<i32>>;
-    type Primitive = ThemedPrim<<Self as PrimFrame>::DirectRender>;
     type Theme = Theme;
 
-    fn upload_primitives<I>(&mut self, _ident: &[WidgetIdent], theme: &Theme, transform: &BoundBox<Point2<i32>>, prim_iter: I)
-        where I: Iterator<Item=ThemedPrim<<GLFrame as PrimFrame>::DirectRender>>
+    fn upload_primitives<'a, I>(&mut self, ident: &[WidgetIdent], theme: &Theme, transform: &BoundBox<Point2<i32>>, prim_iter: I)
+        where I: Iterator<Item=ThemedPrim<'a, <GLFrame as PrimFrame>::DirectRender>>
     {
         self.poly_translator.translate_prims(
             *transform,
@@ -253,6 +642,15 @@ impl RenderFrame for GLFrame {
             prim_iter,
             &mut self.draw
         );
+        self.draw.store_widget_vertices(ident, *transform);
+    }
+
+    fn retain_primitives(&mut self, ident: &[WidgetIdent]) {
+        self.draw.retain_vertices(ident);
+    }
+
+    fn retain_descendant_primitives(&mut self, ident: &[WidgetIdent]) {
+        self.draw.retain_descendant_vertices(ident);
     }
 
     #[inline]
@@ -264,7 +662,7 @@ impl RenderFrame for GLFrame {
 
 const VERT_SHADER: &str = r#"
     #version 330
-    in ivec2 loc;
+    in vec2 loc;
     in vec4 color;
     in vec2 tex_coord;
 
@@ -275,7 +673,7 @@ const VERT_SHADER: &str = r#"
     out vec4 frag_color;
 
     void main() {
-        gl_Position = vec4(vec2(1.0, -1.0) * (vec2(loc) / vec2(window_size) - 0.5) * 2.0, 1.0, 1.0);
+        gl_Position = vec4(vec2(1.0, -1.0) * (loc / vec2(window_size) - 0.5) * 2.0, 1.0, 1.0);
         frag_color = color;
         tex_coord_out = (tex_coord) / vec2(atlas_size);
     }
@@ -294,3 +692,60 @@ const FRAG_SHADER: &str = r#"
         out_color = frag_color * texture(tex_atlas, tex_coord_out);
     }
 "#;
+
+const SHAPE_VERT_SHADER: &str = r#"
+    #version 330
+    in vec2 loc;
+    in vec4 color;
+    in vec2 shape_pos;
+    in vec2 shape_half_dims;
+    in vec2 shape_radius_border;
+    in vec4 border_color;
+
+    uniform uvec2 window_size;
+
+    out vec4 frag_color;
+    out vec2 shape_pos_out;
+    flat out vec2 shape_half_dims_out;
+    flat out vec2 shape_radius_border_out;
+    flat out vec4 border_color_out;
+
+    void main() {
+        gl_Position = vec4(vec2(1.0, -1.0) * (loc / vec2(window_size) - 0.5) * 2.0, 1.0, 1.0);
+        frag_color = color;
+        shape_pos_out = shape_pos;
+        shape_half_dims_out = shape_half_dims;
+        shape_radius_border_out = shape_radius_border;
+        border_color_out = border_color;
+    }
+"#;
+
+const SHAPE_FRAG_SHADER: &str = r#"
+    #version 330
+    in vec4 frag_color;
+    in vec2 shape_pos_out;
+    flat in vec2 shape_half_dims_out;
+    flat in vec2 shape_radius_border_out;
+    flat in vec4 border_color_out;
+
+    out vec4 out_color;
+
+    void main() {
+        // Signed distance from the edge of the rounded rectangle, negative inside of it.
+        float radius = min(shape_radius_border_out.x, min(shape_half_dims_out.x, shape_half_dims_out.y));
+        vec2 corner_dist = abs(shape_pos_out) - shape_half_dims_out + radius;
+        float dist = length(max(corner_dist, 0.0)) + min(max(corner_dist.x, corner_dist.y), 0.0) - radius;
+
+        // Fade out over the pixel straddling each edge, for anti-aliasing.
+        float coverage = clamp(0.5 - dist, 0.0, 1.0);
+        float fill_coverage = clamp(0.5 - (dist + shape_radius_border_out.y), 0.0, 1.0);
+
+        // Mix the border and fill with premultiplied alpha, so that a transparent fill doesn't
+        // darken the inner edge of the border.
+        vec4 border = vec4(border_color_out.rgb * border_color_out.a, border_color_out.a);
+        vec4 fill = vec4(frag_color.rgb * frag_color.a, frag_color.a);
+        vec4 color = mix(border, fill, fill_coverage) * coverage;
+
+        out_color = vec4(color.rgb / max(color.a, 0.00001), color.a);
+    }
+"#;
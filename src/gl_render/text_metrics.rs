@@ -0,0 +1,70 @@
+use gl_render::font_cache::FontCache;
+use gl_render::translate::RenderString;
+use theme::ThemeText;
+
+use cgmath::Point2;
+use cgmath_geometry::DimsBox;
+use glyphydog::{ShapedBuffer, Shaper, DPI};
+
+use std::cell::RefCell;
+
+struct MetricsState {
+    font_cache: FontCache,
+    shaper: Shaper,
+    shaped_text: ShapedBuffer
+}
+
+/// Shapes and measures text without drawing it.
+///
+/// Widgets can use this to compute their size bounds before they're first rendered. The theme
+/// owns a `TextMetrics`, which can be retrieved with `Theme::text_metrics`.
+pub struct TextMetrics {
+    state: RefCell<MetricsState>
+}
+
+impl TextMetrics {
+    pub fn new() -> TextMetrics {
+        TextMetrics {
+            state: RefCell::new(MetricsState {
+                font_cache: FontCache::new(),
+                shaper: Shaper::new(),
+                shaped_text: ShapedBuffer::new()
+            })
+        }
+    }
+
+    /// Get the size of the area `string` covers when drawn with `text_style`, including its styled
+    /// spans. The text gets wrapped at `max_width`, if the style allows wrapping.
+    ///
+    /// Returns a zero size if the style's face can't be loaded.
+    pub fn measure(&self, string: &RenderString, text_style: &ThemeText, max_width: Option<i32>) -> DimsBox<Point2<i32>> {
+        let MetricsState {
+            ref mut font_cache,
+            ref mut shaper,
+            ref mut shaped_text
+        } = *self.state.borrow_mut();
+
+        match font_cache.face(text_style.face.clone()) {
+            Ok(_) => string.measure(
+                max_width,
+                text_style,
+                font_cache,
+                shaper,
+                shaped_text,
+                DPI::new(72, 72) // TODO: REPLACE HARDCODED VALUE
+            ),
+            Err(_) => DimsBox::new2(0, 0)
+        }
+    }
+
+    /// Get the size of the area a plain string covers when drawn with `text_style`.
+    pub fn measure_str(&self, string: &str, text_style: &ThemeText, max_width: Option<i32>) -> DimsBox<Point2<i32>> {
+        self.measure(&RenderString::new(string.to_string()), text_style, max_width)
+    }
+
+    /// Get the height of a single line of text drawn with `text_style`. Unlike `measure`, this
+    /// doesn't depend on the text, so it's useful for sizing text fields that may be empty.
+    pub fn line_height(&self, text_style: &ThemeText) -> i32 {
+        self.measure_str(" ", text_style, None).height()
+    }
+}
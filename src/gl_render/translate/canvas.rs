@@ -0,0 +1,684 @@
+use gl_render::GLVertex;
+use gl_render::atlas::AtlasRect;
+use gl_render::translate::RenderString;
+use gullery::glsl::Nu8;
+use gullery::colors::Rgba;
+
+use cgmath::{Point2, Vector2, InnerSpace};
+use cgmath_geometry::{BoundBox, GeoBox};
+
+use arrayvec::ArrayVec;
+
+use theme::{ThemeShape, Fill};
+
+/// The furthest, in pixels, that the line segments approximating a bezier curve can stray from
+/// the curve.
+const CURVE_TOLERANCE: f32 = 0.25;
+/// The maximum number of line segments a single bezier curve gets split into.
+const MAX_CURVE_STEPS: usize = 64;
+
+/// A single drawing operation performed by a `Canvas`.
+///
+/// All positions are in pixels, relative to the top-left corner of the canvas. Anything that falls
+/// outside of the canvas gets clipped.
+#[derive(Debug, Clone)]
+pub enum DrawCommand {
+    /// Draw a straight line between two points.
+    Line {
+        start: Point2<f32>,
+        end: Point2<f32>,
+        stroke: Stroke
+    },
+    /// Draw a series of connected lines. If `closed` is true, the last point gets connected back
+    /// to the first point.
+    Polyline {
+        points: Vec<Point2<f32>>,
+        closed: bool,
+        stroke: Stroke
+    },
+    /// Draw a rectangle, with optionally rounded corners. The stroke is drawn inside of the rect.
+    Rect {
+        rect: BoundBox<Point2<i32>>,
+        fill: Fill,
+        stroke: Option<Stroke>,
+        corner_radius: u16
+    },
+    /// Draw a circle. The stroke is drawn inside of the circle.
+    Circle {
+        center: Point2<i32>,
+        radius: u16,
+        fill: Fill,
+        stroke: Option<Stroke>
+    },
+    /// Draw a path made of lines and bezier curves.
+    ///
+    /// Each of the path's subpaths gets filled independently, so overlapping subpaths can't be used
+    /// to cut holes into each other.
+    Path {
+        path: Path,
+        fill: Option<Rgba<Nu8>>,
+        stroke: Option<Stroke>
+    },
+    /// Draw text in the given rect, using the text style of the given theme path.
+    Text {
+        rect: BoundBox<Point2<i32>>,
+        string: RenderString,
+        theme_path: String
+    },
+    /// Draw the image of the given theme path in the given rect.
+    Image {
+        rect: BoundBox<Point2<i32>>,
+        theme_path: String
+    }
+}
+
+/// The style used to draw lines and outlines.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stroke {
+    pub color: Rgba<Nu8>,
+    /// The width of the line, in pixels.
+    pub width: f32
+}
+
+/// A shape made out of lines and bezier curves.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Path {
+    segments: Vec<PathSegment>
+}
+
+/// A single part of a `Path`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathSegment {
+    /// Start a new subpath at the given point.
+    MoveTo(Point2<f32>),
+    /// Draw a line from the current point to the given point.
+    LineTo(Point2<f32>),
+    /// Draw a quadratic bezier curve, with the given control and end points.
+    QuadTo(Point2<f32>, Point2<f32>),
+    /// Draw a cubic bezier curve, with the given control and end points.
+    CubicTo(Point2<f32>, Point2<f32>, Point2<f32>),
+    /// Connect the current point back to the start of the subpath.
+    Close
+}
+
+impl DrawCommand {
+    /// Get the shape used to draw a `Rect` or `Circle` command, relative to the canvas.
+    pub(in gl_render) fn shape(&self) -> Option<(BoundBox<Point2<i32>>, ThemeShape)> {
+        let shape = |fill: Fill, stroke: Option<Stroke>, corner_radius: u16| ThemeShape {
+            fill,
+            border_width: stroke.map(|s| s.width.round().max(0.0) as u16).unwrap_or(0),
+            border_color: stroke.map(|s| s.color).unwrap_or(Rgba::new(Nu8(0), Nu8(0), Nu8(0), Nu8(0))),
+            corner_radius
+        };
+
+        match *self {
+            DrawCommand::Rect{rect, fill, stroke, corner_radius} => Some((rect, shape(fill, stroke, corner_radius))),
+            DrawCommand::Circle{center, radius, fill, stroke} => {
+                let radius_i = radius as i32;
+                let rect = BoundBox::new2(center.x - radius_i, center.y - radius_i, center.x + radius_i, center.y + radius_i);
+                Some((rect, shape(fill, stroke, radius)))
+            },
+            _ => None
+        }
+    }
+}
+
+impl Path {
+    #[inline]
+    pub fn new() -> Path {
+        Path::default()
+    }
+
+    #[inline]
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.segments
+    }
+
+    #[inline]
+    pub fn segments_mut(&mut self) -> &mut Vec<PathSegment> {
+        &mut self.segments
+    }
+
+    #[inline]
+    pub fn move_to(&mut self, point: Point2<f32>) {
+        self.segments.push(PathSegment::MoveTo(point));
+    }
+
+    #[inline]
+    pub fn line_to(&mut self, point: Point2<f32>) {
+        self.segments.push(PathSegment::LineTo(point));
+    }
+
+    #[inline]
+    pub fn quad_to(&mut self, ctrl: Point2<f32>, end: Point2<f32>) {
+        self.segments.push(PathSegment::QuadTo(ctrl, end));
+    }
+
+    #[inline]
+    pub fn cubic_to(&mut self, ctrl_0: Point2<f32>, ctrl_1: Point2<f32>, end: Point2<f32>) {
+        self.segments.push(PathSegment::CubicTo(ctrl_0, ctrl_1, end));
+    }
+
+    #[inline]
+    pub fn close(&mut self) {
+        self.segments.push(PathSegment::Close);
+    }
+
+    /// Approximate the path with straight lines, calling `subpath` with the points and closed-ness
+    /// of each subpath.
+    fn flatten(&self, points: &mut Vec<Point2<f32>>, mut subpath: impl FnMut(&[Point2<f32>], bool)) {
+        points.clear();
+        for segment in &self.segments {
+            match *segment {
+                PathSegment::MoveTo(point) => {
+                    if points.len() > 1 {
+                        subpath(points, false);
+                        points.clear();
+                    }
+                    points.push(point);
+                },
+                PathSegment::LineTo(point) => points.push(point),
+                PathSegment::QuadTo(ctrl, end) => {
+                    let start = points.last().cloned().unwrap_or(ctrl);
+                    let steps = curve_steps(&[start, ctrl, end]);
+                    points.extend((1..steps + 1).map(|i| {
+                        let t = i as f32 / steps as f32;
+                        let mt = 1.0 - t;
+                        Point2::new(
+                            mt * mt * start.x + 2.0 * mt * t * ctrl.x + t * t * end.x,
+                            mt * mt * start.y + 2.0 * mt * t * ctrl.y + t * t * end.y
+                        )
+                    }));
+                },
+                PathSegment::CubicTo(ctrl_0, ctrl_1, end) => {
+                    let start = points.last().cloned().unwrap_or(ctrl_0);
+                    let steps = curve_steps(&[start, ctrl_0, ctrl_1, end]);
+                    points.extend((1..steps + 1).map(|i| {
+                        let t = i as f32 / steps as f32;
+                        let mt = 1.0 - t;
+                        let (a, b, c, d) = (mt * mt * mt, 3.0 * mt * mt * t, 3.0 * mt * t * t, t * t * t);
+                        Point2::new(
+                            a * start.x + b * ctrl_0.x + c * ctrl_1.x + d * end.x,
+                            a * start.y + b * ctrl_0.y + c * ctrl_1.y + d * end.y
+                        )
+                    }));
+                },
+                PathSegment::Close => {
+                    if let Some(&first) = points.first() {
+                        subpath(points, true);
+                        points.clear();
+                        points.push(first);
+                    }
+                }
+            }
+        }
+
+        if points.len() > 1 {
+            subpath(points, false);
+        }
+        points.clear();
+    }
+}
+
+/// Get the number of line segments needed to approximate a curve with the given control polygon
+/// within `CURVE_TOLERANCE`, using Wang's formula. Flatter curves need fewer segments.
+fn curve_steps(control_points: &[Point2<f32>]) -> usize {
+    let degree = (control_points.len() - 1) as f32;
+    let max_second_diff = control_points.windows(3)
+        .map(|w| ((w[0] - w[1]) - (w[1] - w[2])).magnitude())
+        .fold(0.0, f32::max);
+    let steps = (degree * (degree - 1.0) / 8.0 * max_second_diff / CURVE_TOLERANCE).sqrt().ceil();
+    (steps as usize).max(1).min(MAX_CURVE_STEPS)
+}
+
+/// Turns canvas drawing commands into triangles.
+///
+/// The buffers are kept around between commands, to avoid reallocating them every time something
+/// gets drawn.
+pub(in gl_render) struct Tessellator {
+    points: Vec<Point2<f32>>,
+    indices: Vec<usize>,
+    vertices: Vec<GLVertex>
+}
+
+impl Tessellator {
+    pub fn new() -> Tessellator {
+        Tessellator {
+            points: Vec::new(),
+            indices: Vec::new(),
+            vertices: Vec::new()
+        }
+    }
+
+    /// Tessellate the lines and paths drawn by `command`, returning an iterator over the vertices
+    /// along with the atlas page they sample from.
+    ///
+    /// `rect` is the canvas's rect, and `clip` is the area the triangles get clipped to.
+    pub fn tessellate<'a>(
+        &'a mut self,
+        command: &DrawCommand,
+        rect: BoundBox<Point2<i32>>,
+        clip: BoundBox<Point2<i32>>,
+        white: AtlasRect
+    ) -> impl 'a + Iterator<Item=(usize, GLVertex)>
+    {
+        let Tessellator {
+            points: ref mut path_points,
+            ref mut indices,
+            ref mut vertices
+        } = *self;
+        vertices.clear();
+
+        let origin = Vector2::new(rect.min().x as f32, rect.min().y as f32);
+        let clip = BoundBox::new2(clip.min().x as f32, clip.min().y as f32, clip.max().x as f32, clip.max().y as f32);
+        let white_coord = Point2::new(white.rect.min().x as f32 + 0.5, white.rect.min().y as f32 + 0.5);
+
+        {
+            let mut push_tri = |color: Rgba<Nu8>, tri: [Point2<f32>; 3]| {
+                let base = GLVertex::new(Point2::new(0, 0), color, white_coord);
+                let poly = clip_triangle([tri[0] + origin, tri[1] + origin, tri[2] + origin], clip);
+                if poly.len() >= 3 {
+                    for i in 1..poly.len() - 1 {
+                        vertices.extend([poly[0], poly[i], poly[i + 1]].iter().map(|&loc| GLVertex{ loc, ..base }));
+                    }
+                }
+            };
+
+            match *command {
+                DrawCommand::Line{start, end, stroke} =>
+                    stroke_polyline(&[start, end], false, stroke.width, |tri| push_tri(stroke.color, tri)),
+                DrawCommand::Polyline{ref points, closed, stroke} =>
+                    stroke_polyline(points, closed, stroke.width, |tri| push_tri(stroke.color, tri)),
+                DrawCommand::Path{ref path, fill, stroke} => {
+                    path.flatten(path_points, |subpath, closed| {
+                        if let Some(fill) = fill {
+                            fill_polygon(subpath, indices, |tri| push_tri(fill, tri));
+                        }
+                        if let Some(stroke) = stroke {
+                            stroke_polyline(subpath, closed, stroke.width, |tri| push_tri(stroke.color, tri));
+                        }
+                    });
+                },
+                DrawCommand::Rect{..}   |
+                DrawCommand::Circle{..} |
+                DrawCommand::Text{..}   |
+                DrawCommand::Image{..} => ()
+            }
+        }
+
+        let page = white.page;
+        vertices.drain(..).map(move |v| (page, v))
+    }
+}
+
+#[inline]
+fn cross(a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+/// Split a line strip into triangles. Lines are joined with bevels.
+///
+/// The triangles don't overlap, so translucent strokes get drawn with an even color. The exception
+/// is joins that are too sharp for their segments' lengths, where the inner sides of the segments
+/// overlap instead of meeting at a point.
+fn stroke_polyline(points: &[Point2<f32>], closed: bool, width: f32, mut push_tri: impl FnMut([Point2<f32>; 3])) {
+    if width <= 0.0 {
+        return;
+    }
+    let half_width = width / 2.0;
+
+    // Zero-length segments don't have a direction, so they can't be stroked.
+    let mut points = points.iter().cloned().collect::<Vec<_>>();
+    points.dedup();
+    if closed && points.len() > 2 && points.first() == points.last() {
+        points.pop();
+    }
+    if points.len() < 2 {
+        return;
+    }
+
+    let num_segments = match closed && points.len() > 2 {
+        true => points.len(),
+        false => points.len() - 1
+    };
+    let segment = |i: usize| points[(i + 1) % points.len()] - points[i];
+    let segment_normal = |i: usize| {
+        let dir = segment(i).normalize();
+        Vector2::new(-dir.y, dir.x) * half_width
+    };
+
+    // The corners of each segment's quad: the start and end points offset along the segment's
+    // normal, and then offset against it.
+    let mut corners: Vec<[Point2<f32>; 4]> = (0..num_segments).map(|i| {
+        let (start, end, normal) = (points[i], points[(i + 1) % points.len()], segment_normal(i));
+        [start + normal, end + normal, start - normal, end - normal]
+    }).collect();
+
+    let num_joins = match num_segments == points.len() {
+        true => num_segments,
+        false => num_segments - 1
+    };
+    for i in 0..num_joins {
+        let next = (i + 1) % num_segments;
+        let point = points[next];
+        let (normal, next_normal) = (segment_normal(i), segment_normal(next));
+        let turn = cross(segment(i), segment(next));
+        if turn.abs() <= ::std::f32::EPSILON {
+            continue;
+        }
+
+        // The line turns towards its inner side, where the two segments' edges cross. Moving the
+        // segments' inner corners to the crossing point keeps them from overlapping, and the bevel
+        // fills in the gap between their outer corners.
+        let miter = (normal + next_normal) * (half_width * half_width / (half_width * half_width + normal.dot(next_normal)));
+        let (inner_side, outer_corners) = match turn > 0.0 {
+            true => (0, [point - normal, point - next_normal]),
+            false => (2, [point + normal, point + next_normal])
+        };
+        let inner_corner = match inner_side {
+            0 => point + miter,
+            _ => point - miter
+        };
+
+        // The inner corner can only move so far back along each segment before the segment's
+        // quad folds over on itself.
+        let miter_depth = miter.dot(segment(i).normalize()).abs();
+        if miter_depth.is_finite() && miter_depth <= segment(i).magnitude() / 2.0 && miter_depth <= segment(next).magnitude() / 2.0 {
+            corners[i][inner_side + 1] = inner_corner;
+            corners[next][inner_side] = inner_corner;
+            push_tri([outer_corners[0], outer_corners[1], inner_corner]);
+        } else {
+            push_tri([outer_corners[0], outer_corners[1], point]);
+        }
+    }
+
+    for &[start_pos, end_pos, start_neg, end_neg] in &corners {
+        push_tri([start_pos, end_pos, end_neg]);
+        push_tri([end_neg, start_neg, start_pos]);
+    }
+}
+
+/// Split a simple polygon into triangles, using ear clipping.
+///
+/// Self-intersecting polygons don't have any ears left at some point, at which point the remaining
+/// vertices get drawn as a triangle fan.
+fn fill_polygon(points: &[Point2<f32>], indices: &mut Vec<usize>, mut push_tri: impl FnMut([Point2<f32>; 3])) {
+    indices.clear();
+    indices.extend(0..points.len());
+    indices.dedup_by_key(|i| points[*i]);
+    if indices.len() > 1 && points[indices[0]] == points[*indices.last().unwrap()] {
+        indices.pop();
+    }
+    if indices.len() < 3 {
+        return;
+    }
+
+    let area: f32 = (0..indices.len()).map(|i| {
+        let (a, b) = (points[indices[i]], points[indices[(i + 1) % indices.len()]]);
+        a.x * b.y - b.x * a.y
+    }).sum();
+    if area == 0.0 {
+        return;
+    }
+
+    let len = indices.len();
+    let point = |i: usize| points[indices[i]];
+    // Reflex corners point into the polygon, so they can't be cut off.
+    let is_reflex = |a: Point2<f32>, b: Point2<f32>, c: Point2<f32>| cross(b - a, c - b) * area <= 0.0;
+
+    // The remaining vertices form a ring, which each clipped ear's tip gets unlinked from.
+    let mut prev: Vec<usize> = (0..len).map(|i| (i + len - 1) % len).collect();
+    let mut next: Vec<usize> = (0..len).map(|i| (i + 1) % len).collect();
+    // Only reflex vertices can lie inside of an ear, so they're the only ones that get checked.
+    // Clipping an ear can only turn its neighbors convex, so nothing else needs to be updated.
+    let mut reflex: Vec<bool> = (0..len).map(|i| is_reflex(point(prev[i]), point(i), point(next[i]))).collect();
+
+    let is_ear = |next: &[usize], reflex: &[bool], prev_i: usize, cur: usize, next_i: usize| {
+        if reflex[cur] {
+            return false;
+        }
+
+        let (a, b, c) = (point(prev_i), point(cur), point(next_i));
+        let contains = |p: Point2<f32>| {
+            let (d0, d1, d2) = (cross(b - a, p - a), cross(c - b, p - b), cross(a - c, p - c));
+            (d0 * area >= 0.0) && (d1 * area >= 0.0) && (d2 * area >= 0.0)
+        };
+        let mut i = next[next_i];
+        while i != prev_i {
+            let p = point(i);
+            if reflex[i] && p != a && p != b && p != c && contains(p) {
+                return false;
+            }
+            i = next[i];
+        }
+        true
+    };
+
+    // Walk around the ring, clipping ears as they're found. If every remaining vertex gets visited
+    // without finding an ear, there aren't any left.
+    let (mut remaining, mut cur, mut visited) = (len, 0, 0);
+    while remaining > 3 && visited < remaining {
+        let (prev_i, next_i) = (prev[cur], next[cur]);
+        if is_ear(&next, &reflex, prev_i, cur, next_i) {
+            push_tri([point(prev_i), point(cur), point(next_i)]);
+            next[prev_i] = next_i;
+            prev[next_i] = prev_i;
+            reflex[prev_i] = is_reflex(point(prev[prev_i]), point(prev_i), point(next_i));
+            reflex[next_i] = is_reflex(point(prev_i), point(next_i), point(next[next_i]));
+            remaining -= 1;
+            cur = prev_i;
+            visited = 0;
+        } else {
+            cur = next_i;
+            visited += 1;
+        }
+    }
+
+    let mut i = next[cur];
+    while next[i] != cur {
+        push_tri([point(cur), point(i), point(next[i])]);
+        i = next[i];
+    }
+}
+
+/// Clip a triangle to a rect, returning the clipped polygon's vertices.
+fn clip_triangle(tri: [Point2<f32>; 3], clip: BoundBox<Point2<f32>>) -> ArrayVec<[Point2<f32>; 8]> {
+    let mut poly: ArrayVec<[Point2<f32>; 8]> = tri.iter().cloned().collect();
+
+    // Each function returns how far inside of the edge a point is.
+    let edges: [&Fn(Point2<f32>) -> f32; 4] = [
+        &|p| p.x - clip.min.x,
+        &|p| clip.max.x - p.x,
+        &|p| p.y - clip.min.y,
+        &|p| clip.max.y - p.y
+    ];
+
+    for edge in edges.iter() {
+        if poly.len() == 0 {
+            break;
+        }
+
+        let mut clipped = ArrayVec::new();
+        for i in 0..poly.len() {
+            let (cur, next) = (poly[i], poly[(i + 1) % poly.len()]);
+            let (cur_dist, next_dist) = (edge(cur), edge(next));
+
+            if cur_dist >= 0.0 {
+                clipped.push(cur);
+            }
+            if (cur_dist >= 0.0) != (next_dist >= 0.0) {
+                let t = cur_dist / (cur_dist - next_dist);
+                clipped.push(cur + (next - cur) * t);
+            }
+        }
+        poly = clipped;
+    }
+
+    poly
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tri_area(tri: [Point2<f32>; 3]) -> f32 {
+        cross(tri[1] - tri[0], tri[2] - tri[0]).abs() / 2.0
+    }
+
+    fn assert_area(expected: f32, tris: &[[Point2<f32>; 3]]) {
+        let area: f32 = tris.iter().map(|&tri| tri_area(tri)).sum();
+        assert!((expected - area).abs() < 0.001, "expected area {}, got {}", expected, area);
+    }
+
+    fn flatten(path: &Path) -> Vec<(Vec<Point2<f32>>, bool)> {
+        let mut subpaths = Vec::new();
+        path.flatten(&mut Vec::new(), |points, closed| subpaths.push((points.to_vec(), closed)));
+        subpaths
+    }
+
+    #[test]
+    fn clip_triangle_inside() {
+        let tri = [Point2::new(1.0, 1.0), Point2::new(4.0, 1.0), Point2::new(1.0, 4.0)];
+        let poly = clip_triangle(tri, BoundBox::new2(0.0, 0.0, 8.0, 8.0));
+        assert_eq!(&tri[..], &poly[..]);
+    }
+
+    #[test]
+    fn clip_triangle_outside() {
+        let tri = [Point2::new(10.0, 10.0), Point2::new(14.0, 10.0), Point2::new(10.0, 14.0)];
+        assert_eq!(0, clip_triangle(tri, BoundBox::new2(0.0, 0.0, 8.0, 8.0)).len());
+    }
+
+    #[test]
+    fn clip_triangle_corner() {
+        // The triangle's corners stick out of the clip rect, leaving a pentagon inside of it.
+        let tri = [Point2::new(0.0, 0.0), Point2::new(8.0, 0.0), Point2::new(0.0, 8.0)];
+        let clip = BoundBox::new2(1.0, 1.0, 6.0, 6.0);
+        let poly = clip_triangle(tri, clip);
+
+        assert_eq!(5, poly.len());
+        assert!(poly.iter().all(|p| clip.min.x <= p.x && p.x <= clip.max.x && clip.min.y <= p.y && p.y <= clip.max.y));
+        let fan = (1..poly.len() - 1).map(|i| [poly[0], poly[i], poly[i + 1]]).collect::<Vec<_>>();
+        assert_area(17.0, &fan);
+    }
+
+    #[test]
+    fn fill_square() {
+        let square = [Point2::new(0.0, 0.0), Point2::new(4.0, 0.0), Point2::new(4.0, 4.0), Point2::new(0.0, 4.0)];
+        let mut tris = Vec::new();
+        fill_polygon(&square, &mut Vec::new(), |tri| tris.push(tri));
+
+        assert_eq!(2, tris.len());
+        assert_area(16.0, &tris);
+    }
+
+    #[test]
+    fn fill_concave() {
+        let l_shape = [
+            Point2::new(0.0, 0.0), Point2::new(4.0, 0.0), Point2::new(4.0, 1.0),
+            Point2::new(1.0, 1.0), Point2::new(1.0, 4.0), Point2::new(0.0, 4.0)
+        ];
+
+        // The triangles cover the polygon exactly, regardless of winding order.
+        for reverse in [false, true].iter() {
+            let mut points = l_shape.to_vec();
+            if *reverse {
+                points.reverse();
+            }
+            let mut tris = Vec::new();
+            fill_polygon(&points, &mut Vec::new(), |tri| tris.push(tri));
+
+            assert_eq!(4, tris.len());
+            assert_area(7.0, &tris);
+        }
+    }
+
+    #[test]
+    fn fill_degenerate() {
+        let line = [Point2::new(0.0, 0.0), Point2::new(2.0, 0.0), Point2::new(4.0, 0.0)];
+        let mut tris = Vec::new();
+        fill_polygon(&line, &mut Vec::new(), |tri| tris.push(tri));
+        assert_eq!(0, tris.len());
+    }
+
+    #[test]
+    fn stroke_line() {
+        let mut tris = Vec::new();
+        stroke_polyline(&[Point2::new(0.0, 0.0), Point2::new(10.0, 0.0)], false, 2.0, |tri| tris.push(tri));
+
+        assert_eq!(2, tris.len());
+        assert_area(20.0, &tris);
+    }
+
+    #[test]
+    fn stroke_join_doesnt_overlap() {
+        let points = [Point2::new(0.0, 0.0), Point2::new(10.0, 0.0), Point2::new(10.0, 10.0)];
+        let mut tris = Vec::new();
+        stroke_polyline(&points, false, 2.0, |tri| tris.push(tri));
+
+        // Two segment quads, along with the bevel on the outside of the corner. If any of the
+        // triangles overlapped, their areas would add up to more than the stroke's area.
+        assert_eq!(5, tris.len());
+        assert_area(39.5, &tris);
+    }
+
+    #[test]
+    fn flatten_lines() {
+        let mut path = Path::new();
+        path.move_to(Point2::new(0.0, 0.0));
+        path.line_to(Point2::new(4.0, 0.0));
+        path.line_to(Point2::new(4.0, 4.0));
+        path.close();
+        path.move_to(Point2::new(8.0, 8.0));
+        path.line_to(Point2::new(9.0, 9.0));
+
+        let subpaths = flatten(&path);
+        assert_eq!(2, subpaths.len());
+        assert_eq!((vec![Point2::new(0.0, 0.0), Point2::new(4.0, 0.0), Point2::new(4.0, 4.0)], true), subpaths[0]);
+        assert_eq!((vec![Point2::new(8.0, 8.0), Point2::new(9.0, 9.0)], false), subpaths[1]);
+    }
+
+    #[test]
+    fn flatten_curves() {
+        // A curve with collinear, evenly-spaced control points is a straight line.
+        let mut flat = Path::new();
+        flat.move_to(Point2::new(0.0, 0.0));
+        flat.quad_to(Point2::new(5.0, 0.0), Point2::new(10.0, 0.0));
+        assert_eq!(vec![(vec![Point2::new(0.0, 0.0), Point2::new(10.0, 0.0)], false)], flatten(&flat));
+
+        let mut curved = Path::new();
+        curved.move_to(Point2::new(0.0, 0.0));
+        curved.cubic_to(Point2::new(0.0, 100.0), Point2::new(100.0, 100.0), Point2::new(100.0, 0.0));
+        let subpaths = flatten(&curved);
+        let points = &subpaths[0].0;
+        assert!(2 < points.len() && points.len() <= MAX_CURVE_STEPS + 1);
+        assert_eq!(Point2::new(100.0, 0.0), *points.last().unwrap());
+    }
+
+    #[test]
+    fn command_shape() {
+        let stroke = Stroke {
+            color: Rgba::new(Nu8(255), Nu8(0), Nu8(0), Nu8(255)),
+            width: 2.4
+        };
+        let circle = DrawCommand::Circle {
+            center: Point2::new(10, 10),
+            radius: 5,
+            fill: Fill::None,
+            stroke: Some(stroke)
+        };
+
+        let (rect, shape) = circle.shape().unwrap();
+        assert_eq!(BoundBox::new2(5, 5, 15, 15), rect);
+        assert_eq!(5, shape.corner_radius);
+        assert_eq!(2, shape.border_width);
+        assert_eq!(stroke.color, shape.border_color);
+
+        let line = DrawCommand::Line {
+            start: Point2::new(0.0, 0.0),
+            end: Point2::new(1.0, 1.0),
+            stroke
+        };
+        assert!(line.shape().is_none());
+    }
+}
@@ -1,4 +1,5 @@
 use gl_render::GLVertex;
+use gl_render::atlas::AtlasRect;
 use gullery::glsl::Nu8;
 use gullery::colors::Rgba;
 
@@ -9,8 +10,10 @@ use theme::RescaleRules;
 
 use dct::layout::{Align, Margins};
 
+/// Iterates over the vertices used to draw an image, along with the atlas page they sample from.
 pub(in gl_render) struct ImageTranslate {
     verts: TranslateVerts,
+    page: usize,
     cur_vertex: usize
 }
 
@@ -31,11 +34,14 @@ enum TranslateVerts {
 }
 
 impl ImageTranslate {
-    pub fn new(rect: BoundBox<Point2<i32>>, clip: BoundBox<Point2<i32>>, atlas_rect: OffsetBox<Point2<u16>>, color: Rgba<Nu8>, rescale: RescaleRules) -> ImageTranslate {
+    pub fn new(rect: BoundBox<Point2<i32>>, clip: BoundBox<Point2<i32>>, atlas_rect: AtlasRect, color: Rgba<Nu8>, rescale: RescaleRules) -> ImageTranslate {
+        let page = atlas_rect.page;
+        let atlas_rect = atlas_rect.rect.cast::<u16>().unwrap_or(OffsetBox::new2(0, 0, 0, 0));
         let clipped_rect = match clip.intersect_rect(rect) {
             Some(clipped_rect) => clipped_rect,
             None => return ImageTranslate {
                 verts: TranslateVerts::None,
+                page,
                 cur_vertex: 0
             }
         };
@@ -59,43 +65,43 @@ impl ImageTranslate {
         atlas_rect_clipped.min.y += atlas_clip_margins.top;
         atlas_rect_clipped.max.y -= atlas_clip_margins.bottom;
 
-        let tl_out = GLVertex {
-            loc: min,
+        let tl_out = GLVertex::new(
+            min,
             color,
-            tex_coord: atlas_rect_clipped.min()
-        };
-        let tr_out = GLVertex {
-            loc: Point2::new(max.x, min.y),
+            atlas_rect_clipped.min()
+        );
+        let tr_out = GLVertex::new(
+            Point2::new(max.x, min.y),
             color,
-            tex_coord: Point2::new(atlas_rect_clipped.max().x, atlas_rect_clipped.min().y)
-        };
-        let br_out = GLVertex {
-            loc: max,
+            Point2::new(atlas_rect_clipped.max().x, atlas_rect_clipped.min().y)
+        );
+        let br_out = GLVertex::new(
+            max,
             color,
-            tex_coord: atlas_rect_clipped.max()
-        };
-        let bl_out = GLVertex {
-            loc: Point2::new(min.x, max.y),
+            atlas_rect_clipped.max()
+        );
+        let bl_out = GLVertex::new(
+            Point2::new(min.x, max.y),
             color,
-            tex_coord: Point2::new(atlas_rect_clipped.min().x, atlas_rect_clipped.max().y)
-        };
+            Point2::new(atlas_rect_clipped.min().x, atlas_rect_clipped.max().y)
+        );
 
         macro_rules! derived_verts {
             ($base:expr, $sign_x:tt ($loc_slice_x:expr, $atlas_slice_x:expr), $sign_y:tt ($loc_slice_y:expr, $atlas_slice_y:expr)) => {{
                 [
                     $base,
                     GLVertex {
-                        loc: Point2::new($base.loc.x $sign_x $loc_slice_x as i32, $base.loc.y),
+                        loc: Point2::new($base.loc.x $sign_x $loc_slice_x as f32, $base.loc.y),
                         tex_coord: Point2::new($base.tex_coord.x $sign_x $atlas_slice_x $sign_x 0.5, $base.tex_coord.y),
                         ..$base
                     },
                     GLVertex {
-                        loc: Point2::new($base.loc.x $sign_x $loc_slice_x as i32, $base.loc.y $sign_y $loc_slice_y as i32),
+                        loc: Point2::new($base.loc.x $sign_x $loc_slice_x as f32, $base.loc.y $sign_y $loc_slice_y as f32),
                         tex_coord: Point2::new($base.tex_coord.x $sign_x $atlas_slice_x $sign_x 0.5, $base.tex_coord.y $sign_y $atlas_slice_y $sign_y 0.5),
                         ..$base
                     },
                     GLVertex {
-                        loc: Point2::new($base.loc.x, $base.loc.y $sign_y $loc_slice_y as i32),
+                        loc: Point2::new($base.loc.x, $base.loc.y $sign_y $loc_slice_y as f32),
                         tex_coord: Point2::new($base.tex_coord.x, $base.tex_coord.y $sign_y $atlas_slice_y $sign_y 0.5),
                         ..$base
                     },
@@ -163,19 +169,19 @@ impl ImageTranslate {
 
                 TranslateVerts::Stretch {
                     tl: GLVertex {
-                        loc: bounds.min(),
+                        loc: Point2::new(bounds.min.x as f32, bounds.min.y as f32),
                         ..tl_out
                     },
                     tr: GLVertex {
-                        loc: Point2::new(bounds.max.x, bounds.min.y),
+                        loc: Point2::new(bounds.max.x as f32, bounds.min.y as f32),
                         ..tr_out
                     },
                     br: GLVertex {
-                        loc: bounds.max,
+                        loc: Point2::new(bounds.max.x as f32, bounds.max.y as f32),
                         ..br_out
                     },
                     bl: GLVertex {
-                        loc: Point2::new(bounds.min.x, bounds.max.y),
+                        loc: Point2::new(bounds.min.x as f32, bounds.max.y as f32),
                         ..bl_out
                     }
                 }
@@ -184,16 +190,17 @@ impl ImageTranslate {
 
         ImageTranslate {
             verts,
+            page,
             cur_vertex: 0
         }
     }
 }
 
 impl Iterator for ImageTranslate {
-    type Item = GLVertex;
+    type Item = (usize, GLVertex);
 
     #[inline]
-    fn next(&mut self) -> Option<GLVertex> {
+    fn next(&mut self) -> Option<(usize, GLVertex)> {
         let ret = match self.verts {
             TranslateVerts::Stretch{tl, tr, br, bl} => {
                 let tris = [
@@ -239,6 +246,6 @@ impl Iterator for ImageTranslate {
         };
 
         self.cur_vertex += 1;
-        ret
+        ret.map(|vertex| (self.page, vertex))
     }
 }
@@ -1,32 +1,38 @@
+mod canvas;
 mod image;
+mod shape;
 mod text;
 
 use cgmath::Point2;
 use cgmath_geometry::{GeoBox, OffsetBox, BoundBox};
-use glyphydog::{ShapedBuffer, Shaper, FaceSize, DPI};
+use glyphydog::{ShapedBuffer, Shaper, DPI};
 
 use gullery::glsl::{Nu8, Ni32};
 use gullery::colors::Rgba;
+use gullery::framebuffer::DefaultFramebuffer;
 
 use gl_render::{FrameDraw, GLFrame, PrimFrame};
 
-use theme::Theme;
+use theme::{Theme, ThemeShape};
 use core::render::Theme as CoreTheme;
 
+use self::canvas::Tessellator;
 use self::image::ImageTranslate;
+use self::shape::ShapeTranslate;
 use self::text::TextTranslate;
 
-pub use self::text::{EditString, RenderString};
+pub use self::canvas::{DrawCommand, Path, PathSegment, Stroke};
+pub use self::text::{EditString, RenderString, TextSpan};
 
-use std::mem;
+use std::{fmt, mem};
 
 
-#[derive(Debug, PartialEq)]
-pub struct ThemedPrim<D> {
-    pub theme_path: *const str,
+#[derive(Debug)]
+pub struct ThemedPrim<'a, D: 'a> {
+    pub theme_path: &'a str,
     pub min: Point2<RelPoint>,
     pub max: Point2<RelPoint>,
-    pub prim: Prim<D>
+    pub prim: Prim<'a, D>
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -35,12 +41,29 @@ pub struct RelPoint {
     pub pixel_pos: i32
 }
 
-#[derive(Debug, PartialEq, Eq)]
-pub enum Prim<D> {
+pub enum Prim<'a, D: 'a> {
+    /// Draw the theme's shape and image.
     Image,
-    String(*mut RenderString),
-    EditString(*mut EditString),
-    DirectRender(*mut FnMut(&mut D))
+    /// Draw a shape, ignoring the theme.
+    Shape(ThemeShape),
+    String(&'a mut RenderString),
+    EditString(&'a mut EditString),
+    /// Draw a list of canvas commands, positioned relative to the top-left corner of the prim.
+    Canvas(&'a mut [DrawCommand]),
+    DirectRender(&'a mut FnMut(&mut D))
+}
+
+impl<'a, D> fmt::Debug for Prim<'a, D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Prim::Image => write!(f, "Image"),
+            Prim::Shape(ref shape) => f.debug_tuple("Shape").field(shape).finish(),
+            Prim::String(ref string) => f.debug_tuple("String").field(string).finish(),
+            Prim::EditString(ref string) => f.debug_tuple("EditString").field(string).finish(),
+            Prim::Canvas(ref commands) => f.debug_tuple("Canvas").field(commands).finish(),
+            Prim::DirectRender(_) => write!(f, "DirectRender(..)")
+        }
+    }
 }
 
 impl RelPoint {
@@ -54,22 +77,24 @@ impl RelPoint {
 pub struct Translator {
     shaped_text: ShapedBuffer,
     shaper: Shaper,
+    tessellator: Tessellator
 }
 
 impl Translator {
     pub fn new() -> Translator {
         Translator {
             shaped_text: ShapedBuffer::new(),
-            shaper: Shaper::new()
+            shaper: Shaper::new(),
+            tessellator: Tessellator::new()
         }
     }
 
-    pub(in gl_render) fn translate_prims(
+    pub(in gl_render) fn translate_prims<'a>(
         &mut self,
         parent_rect: BoundBox<Point2<i32>>,
         theme: &Theme,
         dpi: DPI,
-        prims: impl IntoIterator<Item=ThemedPrim<<GLFrame as PrimFrame>::DirectRender>>,
+        prims: impl IntoIterator<Item=ThemedPrim<'a, <GLFrame as PrimFrame>::DirectRender>>,
 
         draw: &mut FrameDraw
     ) {
@@ -89,42 +114,42 @@ impl Translator {
         });
 
         for (abs_rect, prim) in prim_rect_iter {
-            let theme_path = unsafe{ &*prim.theme_path };
+            let theme_path = prim.theme_path;
             let widget_theme = theme.widget_theme(theme_path);
 
             match (prim.prim, widget_theme.image, widget_theme.text) {
-                (Prim::Image, Some(image), _) => {
-                    let atlas_rect = draw.atlas.image_rect(theme_path, || (&image.pixels, image.dims)).cast::<u16>().unwrap();
-
-                    draw.vertices.extend(ImageTranslate::new(
-                        abs_rect,
-                        parent_rect,
-                        atlas_rect,
-                        Rgba::new(Nu8(255), Nu8(255), Nu8(255), Nu8(255)),
-                        image.rescale
-                    ));
+                (Prim::Image, image_opt, _) => {
+                    if let Some(shape) = widget_theme.shape {
+                        draw.vertices.extend(ShapeTranslate::new(abs_rect, parent_rect, shape));
+                    }
+
+                    if let Some(image) = image_opt {
+                        let atlas_rect = draw.atlas.image_rect(theme_path, &image);
+
+                        draw.vertices.extend(ImageTranslate::new(
+                            abs_rect,
+                            parent_rect,
+                            atlas_rect,
+                            Rgba::new(Nu8(255), Nu8(255), Nu8(255), Nu8(255)),
+                            image.rescale
+                        ));
+                    }
+                },
+                (Prim::Shape(shape), _, _) => {
+                    draw.vertices.extend(ShapeTranslate::new(abs_rect, parent_rect, shape));
                 },
                 (Prim::String(render_string), _, Some(theme_text)) => {
                     match draw.font_cache.face(theme_text.face.clone()) {
-                        Ok(face) => {
-                            let render_string = unsafe{ &mut *render_string };
-
+                        Ok(_) => {
                             draw.vertices.extend(TextTranslate::new_rs(
+                                abs_rect,
                                 abs_rect,
                                 theme_text.clone(),
-                                face,
+                                &mut draw.font_cache,
                                 dpi,
                                 &mut draw.atlas,
-                                |string, face| {
-                                    self.shaper.shape_text(
-                                        string,
-                                        face,
-                                        FaceSize::new(theme_text.face_size, theme_text.face_size),
-                                        dpi,
-                                        &mut self.shaped_text
-                                    ).ok();
-                                    &self.shaped_text
-                                },
+                                &mut self.shaper,
+                                &mut self.shaped_text,
                                 render_string
                             ));
                         },
@@ -135,25 +160,16 @@ impl Translator {
                 },
                 (Prim::EditString(edit_string), _, Some(theme_text)) => {
                     match draw.font_cache.face(theme_text.face.clone()) {
-                        Ok(face) => {
-                            let edit_string = unsafe{ &mut *edit_string };
-
+                        Ok(_) => {
                             draw.vertices.extend(TextTranslate::new_es(
+                                abs_rect,
                                 abs_rect,
                                 theme_text.clone(),
-                                face,
+                                &mut draw.font_cache,
                                 dpi,
                                 &mut draw.atlas,
-                                |string, face| {
-                                    self.shaper.shape_text(
-                                        string,
-                                        face,
-                                        FaceSize::new(theme_text.face_size, theme_text.face_size),
-                                        dpi,
-                                        &mut self.shaped_text
-                                    ).ok();
-                                    &self.shaped_text
-                                },
+                                &mut self.shaper,
+                                &mut self.shaped_text,
                                 edit_string
                             ));
                         },
@@ -162,11 +178,76 @@ impl Translator {
                         }
                     }
                 },
+                (Prim::Canvas(commands), _, _) => {
+                    let clip = match parent_rect.intersect_rect(abs_rect) {
+                        Some(clip) => clip,
+                        None => continue
+                    };
+                    let offset_rect = |rect: BoundBox<Point2<i32>>| BoundBox::new2(
+                        rect.min.x + abs_rect.min.x,
+                        rect.min.y + abs_rect.min.y,
+                        rect.max.x + abs_rect.min.x,
+                        rect.max.y + abs_rect.min.y
+                    );
+
+                    for command in commands {
+                        if let Some((rect, shape)) = command.shape() {
+                            draw.vertices.extend(ShapeTranslate::new(offset_rect(rect), clip, shape));
+                            continue;
+                        }
+
+                        match *command {
+                            DrawCommand::Text{rect, ref mut string, ref theme_path} => {
+                                let theme_text = match theme.widget_theme(theme_path).text {
+                                    Some(theme_text) => theme_text,
+                                    None => continue
+                                };
+                                match draw.font_cache.face(theme_text.face.clone()) {
+                                    Ok(_) => {
+                                        draw.vertices.extend(TextTranslate::new_rs(
+                                            offset_rect(rect),
+                                            clip,
+                                            theme_text,
+                                            &mut draw.font_cache,
+                                            dpi,
+                                            &mut draw.atlas,
+                                            &mut self.shaper,
+                                            &mut self.shaped_text,
+                                            string
+                                        ));
+                                    },
+                                    Err(_) => {
+                                        //TODO: log
+                                    }
+                                }
+                            },
+                            DrawCommand::Image{rect, ref theme_path} => {
+                                if let Some(image) = theme.widget_theme(theme_path).image {
+                                    let atlas_rect = draw.atlas.image_rect(theme_path, &image);
+
+                                    draw.vertices.extend(ImageTranslate::new(
+                                        offset_rect(rect),
+                                        clip,
+                                        atlas_rect,
+                                        Rgba::new(Nu8(255), Nu8(255), Nu8(255), Nu8(255)),
+                                        image.rescale
+                                    ));
+                                }
+                            },
+                            _ => {
+                                let white = draw.atlas.white();
+                                draw.vertices.extend(self.tessellator.tessellate(command, abs_rect, clip, white));
+                            }
+                        }
+                    }
+                },
                 (Prim::DirectRender(render_fn), _, _) => {
+                    // Directly-rendered contents can't be retained between frames.
+                    draw.needs_full_redraw = true;
                     draw.draw_contents();
-                    let render_fn = unsafe{ &mut *render_fn };
-                    let mut framebuffer = unsafe{ mem::uninitialized() };
-                    mem::swap(&mut framebuffer, &mut draw.fb);
+                    // The default framebuffer is just a handle, so a new one can stand in for it while
+                    // the render function owns it.
+                    let framebuffer = mem::replace(&mut draw.fb, DefaultFramebuffer::new(draw.context_state.clone()));
 
                     let viewport_origin = Point2::new(abs_rect.min().x.max(0) as u32, abs_rect.min().y.max(0) as u32);
                     let viewport_rect = OffsetBox::new2(
@@ -177,8 +258,7 @@ impl Translator {
                     );
                     let mut draw_tuple = (framebuffer, viewport_rect, draw.context_state.clone());
                     render_fn(&mut draw_tuple);
-                    mem::swap(&mut draw_tuple.0, &mut draw.fb);
-                    mem::forget(draw_tuple.0);
+                    draw.fb = draw_tuple.0;
                 }
                 _ => {
                 } //TODO: log
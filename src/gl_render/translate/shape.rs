@@ -0,0 +1,103 @@
+use gl_render::ShapeVertex;
+use gullery::glsl::Nu8;
+use gullery::colors::Rgba;
+
+use cgmath::{Point2, Vector2};
+use cgmath_geometry::{BoundBox, GeoBox};
+
+use theme::{ThemeShape, Fill};
+
+/// Iterates over the vertices used to draw a shape.
+///
+/// The shape is drawn as a single quad. Its corners, border, and anti-aliased edges are computed in
+/// the fragment shader.
+pub(in gl_render) struct ShapeTranslate {
+    verts: Option<[ShapeVertex; 4]>,
+    cur_vertex: usize
+}
+
+impl ShapeTranslate {
+    pub fn new(rect: BoundBox<Point2<i32>>, clip: BoundBox<Point2<i32>>, shape: ThemeShape) -> ShapeTranslate {
+        let empty = ShapeTranslate {
+            verts: None,
+            cur_vertex: 0
+        };
+        if rect.width() <= 0 || rect.height() <= 0 {
+            return empty;
+        }
+
+        // Cover an extra pixel around the shape, so that the anti-aliased edges don't get cut off.
+        let aa_rect = BoundBox::new2(rect.min.x - 1, rect.min.y - 1, rect.max.x + 1, rect.max.y + 1);
+        let clipped_rect = match clip.intersect_rect(aa_rect) {
+            Some(clipped_rect) => clipped_rect,
+            None => return empty
+        };
+
+        let center = Point2::new(
+            (rect.min.x + rect.max.x) as f32 / 2.0,
+            (rect.min.y + rect.max.y) as f32 / 2.0
+        );
+        let half_dims = Vector2::new(rect.width() as f32 / 2.0, rect.height() as f32 / 2.0);
+
+        let fill_color = |loc: Point2<i32>| {
+            let frac = |pos: i32, min: i32, len: i32| ((pos - min) as f32 / len as f32).max(0.0).min(1.0);
+            match shape.fill {
+                Fill::None => Rgba::new(Nu8(0), Nu8(0), Nu8(0), Nu8(0)),
+                Fill::Color(color) => color,
+                Fill::VerticalGradient{top, bottom} => lerp_color(top, bottom, frac(loc.y, rect.min.y, rect.height())),
+                Fill::HorizontalGradient{left, right} => lerp_color(left, right, frac(loc.x, rect.min.x, rect.width()))
+            }
+        };
+        let vertex = |loc: Point2<i32>| ShapeVertex {
+            loc: Point2::new(loc.x as f32, loc.y as f32),
+            color: fill_color(loc),
+            shape_pos: Point2::new(loc.x as f32 - center.x, loc.y as f32 - center.y),
+            shape_half_dims: half_dims,
+            shape_radius_border: Vector2::new(shape.corner_radius as f32, shape.border_width as f32),
+            border_color: shape.border_color
+        };
+
+        let (min, max) = (clipped_rect.min(), clipped_rect.max());
+        ShapeTranslate {
+            verts: Some([
+                vertex(min),
+                vertex(Point2::new(max.x, min.y)),
+                vertex(max),
+                vertex(Point2::new(min.x, max.y))
+            ]),
+            cur_vertex: 0
+        }
+    }
+}
+
+fn lerp_color(start: Rgba<Nu8>, end: Rgba<Nu8>, t: f32) -> Rgba<Nu8> {
+    let lerp = |start: Nu8, end: Nu8| Nu8((start.0 as f32 + (end.0 as f32 - start.0 as f32) * t).round() as u8);
+    Rgba::new(
+        lerp(start.r, end.r),
+        lerp(start.g, end.g),
+        lerp(start.b, end.b),
+        lerp(start.a, end.a)
+    )
+}
+
+impl Iterator for ShapeTranslate {
+    type Item = ShapeVertex;
+
+    #[inline]
+    fn next(&mut self) -> Option<ShapeVertex> {
+        let ret = match self.verts {
+            Some([tl, tr, br, bl]) => {
+                let tris = [
+                    tl, tr, br,
+                    br, bl, tl
+                ];
+
+                tris.get(self.cur_vertex).cloned()
+            },
+            None => None
+        };
+
+        self.cur_vertex += 1;
+        ret
+    }
+}
@@ -1,21 +1,25 @@
 use gl_render::GLVertex;
 use gl_render::atlas::Atlas;
+use gl_render::font_cache::FontCache;
 use gl_render::translate::image::ImageTranslate;
-use theme::{ThemeText, RescaleRules, LineWrap};
+use theme::{ThemeText, ThemeFace, SpanStyle, RescaleRules, LineWrap, Truncation};
 
 use cgmath::{EuclideanSpace, ElementWise, Point2, Vector2};
-use cgmath_geometry::{BoundBox, DimsBox, OffsetBox, Segment, GeoBox};
+use cgmath_geometry::{BoundBox, DimsBox, Segment, GeoBox};
 
 use gullery::colors::Rgba;
 use gullery::glsl::Nu8;
 
-use glyphydog::{ShapedBuffer, ShapedGlyph, Face, FaceSize, DPI, LoadFlags, RenderMode};
+use glyphydog::{ShapedBuffer, ShapedGlyph, Shaper, FaceSize, DPI, LoadFlags, RenderMode, PixelMode};
 use dct::layout::Align;
 
 use unicode_segmentation::UnicodeSegmentation;
+use unicode_bidi::{BidiInfo, Level};
 
 use itertools::Itertools;
 use std::{cmp, vec};
+use std::borrow::Cow;
+use std::collections::VecDeque;
 use std::cmp::Ordering;
 use std::ops::Range;
 
@@ -26,6 +30,7 @@ pub(in gl_render) struct TextTranslate<'a> {
     glyph_slice_index: usize,
     glyph_slice: &'a [RenderGlyph],
     highlight_range: Range<usize>,
+    underline_range: Range<usize>,
     cursor_pos: Option<usize>,
     string_len: usize,
     offset: Vector2<i32>,
@@ -35,6 +40,8 @@ pub(in gl_render) struct TextTranslate<'a> {
 
     highlight_vertex_iter: Option<ImageTranslate>,
     glyph_vertex_iter: Option<ImageTranslate>,
+    underline_vertex_iter: Option<ImageTranslate>,
+    strikethrough_vertex_iter: Option<ImageTranslate>,
     cursor_vertex_iter: Option<ImageTranslate>
 }
 
@@ -44,28 +51,103 @@ pub struct RenderGlyph {
     highlight_rect: BoundBox<Point2<i32>>,
     str_index: usize,
     glyph_index: Option<u32>,
+    style_index: usize,
+    /// Whether the glyph is part of right-to-left text.
+    rtl: bool
+}
+
+/// A style applied to a byte range within a `RenderString`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TextSpan {
+    pub range: Range<usize>,
+    pub style: SpanStyle
+}
+
+/// The style of a run of glyphs, created by applying a string's spans to its `ThemeText`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RunStyle {
+    face: ThemeFace,
+    face_size: u32,
+    color: Rgba<Nu8>,
+    underline: bool,
+    strikethrough: bool
+}
+
+/// A segment of shaped text ending at a line break opportunity. May contain glyphs from several
+/// styled runs.
+#[derive(Debug)]
+struct StyledSegment {
+    /// Each glyph, along with the character the glyph begins at and the index of its `RunStyle`.
+    glyphs: Vec<(ShapedGlyph, char, usize)>,
+    hard_break: bool
+}
+
+/// The vertical metrics of a line, taken from the tallest face used in the string.
+#[derive(Debug, Default, Clone, Copy)]
+struct LineMetrics {
+    line_height: i32,
+    ascender: i32,
+    descender: i32
 }
 
 #[derive(Debug, Clone)]
 pub struct EditString {
     pub render_string: RenderString,
     pub draw_cursor: bool,
+    mask: Option<char>,
+    max_len: Option<usize>,
     cursor_pos: usize,
     highlight_range: Range<usize>,
+    /// The byte range of the IME's cursor within the composition text.
+    preedit_cursor: Option<(usize, usize)>,
     cursor_target_x_px: Option<i32>,
+    undo_stack: VecDeque<EditState>,
+    redo_stack: Vec<EditState>,
+    last_edit: Option<EditKind>,
 }
 
+/// A snapshot of an `EditString`, used to undo and redo edits.
+#[derive(Debug, Clone)]
+struct EditState {
+    string: String,
+    cursor_pos: usize,
+    highlight_range: Range<usize>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EditKind {
+    /// Characters typed at the cursor. Consecutive typing gets merged into a single undo entry.
+    Typing,
+    Other
+}
+
+/// The maximum number of edits that can be undone.
+const MAX_UNDO_LEN: usize = 256;
+/// The string drawn in place of truncated text.
+const ELLIPSIS: &str = "\u{2026}";
+
 #[derive(Debug, Clone)]
 pub struct RenderString {
     pub offset: Vector2<i32>,
     string: String,
+    spans: Vec<TextSpan>,
+    /// Uncommitted IME composition text, along with the byte range in `string` it's drawn over.
+    /// It only gets used for layout, and isn't part of the string itself. The text in the range
+    /// gets hidden while composing, since committing replaces it.
+    preedit: Option<(Range<usize>, String)>,
     min_size: DimsBox<Point2<i32>>,
     draw_data: Option<StringDrawData>
 }
 
+/// The laid-out glyphs of a `RenderString`, along with the values they were laid out with. The
+/// glyphs get reused until any of those values change.
 #[derive(Debug, Clone)]
 struct StringDrawData {
     shaped_glyphs: Vec<RenderGlyph>,
+    styles: Vec<RunStyle>,
+    string: String,
+    spans: Vec<TextSpan>,
+    mask: Option<char>,
     text_style: ThemeText,
     dpi: DPI,
     draw_rect: BoundBox<Point2<i32>>,
@@ -73,9 +155,12 @@ struct StringDrawData {
 
 struct GlyphDraw<'a> {
     rect: BoundBox<Point2<i32>>,
-    face: &'a mut Face<()>,
+    /// The part of `rect` that glyphs, decorations, and the cursor are drawn within.
+    clip_rect: BoundBox<Point2<i32>>,
+    font_cache: &'a mut FontCache,
     atlas: &'a mut Atlas,
     text_style: ThemeText,
+    styles: &'a [RunStyle],
     dpi: DPI
 }
 
@@ -112,8 +197,8 @@ struct GlyphIter {
 /// 6. All `advance` values must match the advances obtained in the respecting sections.
 #[derive(Debug, PartialEq, Eq)]
 enum GlyphItem {
-    /// A single shaped glyph. Location is relative to word start.
-    Glyph(ShapedGlyph),
+    /// A single shaped glyph, along with the index of its style. Location is relative to word start.
+    Glyph(ShapedGlyph, usize),
     /// A sequence of renderable glyphs.
     Word {
         glyph_count: u32,
@@ -124,7 +209,7 @@ enum GlyphItem {
         glyph_count: u32,
         advance: i32
     },
-    WhitespaceGlyph(ShapedGlyph),
+    WhitespaceGlyph(ShapedGlyph, usize),
     /// Dictates where a new line starts. Contains the horizontal advance of the line,
     /// not including any trailing whitespace.
     Line {
@@ -138,7 +223,8 @@ enum GlyphItem {
     Run(Run),
     /// A character that advances the cursor to the next tab stop in the line.
     Tab {
-        str_index: usize
+        str_index: usize,
+        style_index: usize
     }
 }
 
@@ -155,79 +241,100 @@ struct Run {
 }
 
 impl<'a> TextTranslate<'a> {
-    pub fn new_rs<'b, F>(
+    pub fn new_rs(
         rect: BoundBox<Point2<i32>>,
+        clip_rect: BoundBox<Point2<i32>>,
         text_style: ThemeText,
-        face: &'a mut Face<()>,
+        font_cache: &'a mut FontCache,
         dpi: DPI,
         atlas: &'a mut Atlas,
-        shape_text: F,
+        shaper: &mut Shaper,
+        shaped_text: &mut ShapedBuffer,
         render_string: &'a mut RenderString
     ) -> TextTranslate<'a>
-        where F: FnOnce(&str, &mut Face<()>) -> &'b ShapedBuffer
     {
-        Self::new_raw(rect, text_style, face, dpi, atlas, shape_text, render_string, 0..0, None)
+        Self::new_raw(rect, clip_rect, text_style, font_cache, dpi, atlas, shaper, shaped_text, render_string, None, 0..0, 0..0, None)
     }
 
-    pub fn new_es<'b, F>(
+    pub fn new_es(
         rect: BoundBox<Point2<i32>>,
+        clip_rect: BoundBox<Point2<i32>>,
         text_style: ThemeText,
-        face: &'a mut Face<()>,
+        font_cache: &'a mut FontCache,
         dpi: DPI,
         atlas: &'a mut Atlas,
-        shape_text: F,
+        shaper: &mut Shaper,
+        shaped_text: &mut ShapedBuffer,
         edit_string: &'a mut EditString
     ) -> TextTranslate<'a>
-        where F: FnOnce(&str, &mut Face<()>) -> &'b ShapedBuffer
     {
+        let highlight_range = edit_string.layout_highlight_range();
+        let preedit_range = edit_string.preedit_range();
+        let cursor_pos = match edit_string.draw_cursor && highlight_range.len() == 0 {
+            true => Some(edit_string.layout_cursor_pos()),
+            false => None
+        };
         Self::new_raw(
-            rect, text_style, face, dpi, atlas,
-            shape_text, &mut edit_string.render_string,
-            edit_string.highlight_range.clone(),
-            match edit_string.draw_cursor && edit_string.highlight_range.len() == 0 {
-                true => Some(edit_string.cursor_pos),
-                false => None
-            }
+            rect, clip_rect, text_style, font_cache, dpi, atlas,
+            shaper, shaped_text, &mut edit_string.render_string,
+            edit_string.mask,
+            highlight_range,
+            preedit_range,
+            cursor_pos
         )
     }
 
-    fn new_raw<'b, F>(
+    fn new_raw(
         mut rect: BoundBox<Point2<i32>>,
+        clip_rect: BoundBox<Point2<i32>>,
         text_style: ThemeText,
-        face: &'a mut Face<()>,
+        font_cache: &'a mut FontCache,
         dpi: DPI,
         atlas: &'a mut Atlas,
-        shape_text: F,
+        shaper: &mut Shaper,
+        shaped_text: &mut ShapedBuffer,
         render_string: &'a mut RenderString,
+        mask: Option<char>,
         highlight_range: Range<usize>,
+        underline_range: Range<usize>,
         cursor_pos: Option<usize>,
     ) -> TextTranslate<'a>
-        where F: FnOnce(&str, &mut Face<()>) -> &'b ShapedBuffer
     {
-        let face_size = FaceSize::new(text_style.face_size, text_style.face_size);
-        let font_metrics = face.metrics_sized(face_size, dpi).unwrap();
-        let (ascender, descender) = ((font_metrics.ascender / 64) as i32, (font_metrics.descender / 64) as i32);
+        let (ascender, descender) = {
+            let face = font_cache.face(text_style.face.clone()).unwrap();
+            let face_size = FaceSize::new(text_style.face_size, text_style.face_size);
+            let font_metrics = face.metrics_sized(face_size, dpi).unwrap();
+            ((font_metrics.ascender / 64) as i32, (font_metrics.descender / 64) as i32)
+        };
 
         rect.min.x += text_style.margins.left as i32;
         rect.max.x -= text_style.margins.right as i32;
         rect.min.y += text_style.margins.top as i32;
         rect.max.y -= text_style.margins.bottom as i32;
+        let clip_rect = rect.intersect_rect(clip_rect).unwrap_or(BoundBox::new(rect.min, rect.min));
+
+        let offset = render_string.offset;
+        let string_len = render_string.layout_len();
+        let (glyph_slice, styles) = render_string.reshape_glyphs(rect, &text_style, font_cache, shaper, shaped_text, dpi, mask, cursor_pos);
 
         TextTranslate {
             glyph_slice_index: 0,
             highlight_range,
+            underline_range,
             cursor_pos,
-            offset: render_string.offset,
+            offset,
 
-            string_len: render_string.string.len(),
+            string_len,
             font_ascender: ascender,
             font_descender: descender,
 
-            glyph_slice: render_string.reshape_glyphs(rect, shape_text, &text_style, face, dpi, cursor_pos),
-            glyph_draw: GlyphDraw{ face, atlas, text_style, dpi, rect },
+            glyph_slice,
+            glyph_draw: GlyphDraw{ font_cache, atlas, text_style, styles, dpi, rect, clip_rect },
 
             highlight_vertex_iter: None,
             glyph_vertex_iter: None,
+            underline_vertex_iter: None,
+            strikethrough_vertex_iter: None,
             cursor_vertex_iter: None
         }
     }
@@ -236,28 +343,22 @@ impl<'a> TextTranslate<'a> {
 impl GlyphIter {
     fn new(
         rect: BoundBox<Point2<i32>>,
-        shaped_text: &ShapedBuffer,
+        mut segments: Vec<StyledSegment>,
+        ellipsis: &[(ShapedGlyph, char, usize)],
         text_style: &ThemeText,
-        face: &mut Face<()>,
-        dpi: DPI
+        tab_advance: i32,
+        line_metrics: LineMetrics
     ) -> GlyphIter
     {
         // TODO: CACHE HEAP ALLOC
         let mut glyph_items = Vec::new();
-        let mut segment_index = 0;
 
-        // Compute the tab advance from the tab size and space advance. Used for tab stops.
-        let tab_advance = {
-            let space_glyph_index = face.char_index(' ');
-            let space_glyph_advance = (face.glyph_advance(
-                space_glyph_index,
-                FaceSize::new(text_style.face_size, text_style.face_size),
-                dpi,
-                LoadFlags::empty()
-            ).unwrap() + (1 << 15)) >> 16;
-
-            space_glyph_advance * text_style.tab_size as i32
-        };
+        if text_style.line_wrap == LineWrap::Character {
+            segments = split_segment_characters(segments);
+        }
+        if text_style.line_wrap == LineWrap::None && text_style.truncation != Truncation::None {
+            segments = truncate_segments(segments, ellipsis, rect.width(), tab_advance, text_style.truncation);
+        }
 
         // Initialize the run data and line data.
         let mut run = Run::default();
@@ -265,12 +366,9 @@ impl GlyphIter {
         // The places where a `Line` or `Run` should be inserted into `glyph_items`.
         let (mut line_insert_index, mut run_insert_index) = (glyph_items.len(), glyph_items.len());
 
-        while let Some(segment) = shaped_text.get_segment(segment_index) {
+        for segment in &segments {
             // Create an iterator over every glyph in the segment.
-            let mut glyphs = {
-                let glyph_with_char = |glyph: ShapedGlyph| (glyph, segment.text[glyph.word_str_index..].chars().next().unwrap());
-                segment.shaped_glyphs.iter().cloned().map(glyph_with_char).peekable()
-            };
+            let mut glyphs = segment.glyphs.iter().cloned().peekable();
             // Contains information about the segment's advances. How it's handled depends on where the line breaks.
             let mut segment_run = Run::default();
             // The number of `Word` and `Whitespace` items in the segment.
@@ -285,10 +383,10 @@ impl GlyphIter {
                 let (mut glyph_count, mut word_advance) = (0, 0);
 
                 // Continue taking glyphs until we hit whitespace.
-                for (glyph, _) in glyphs.peeking_take_while(|&(_, c)| !c.is_whitespace()) {
+                for (glyph, _, style_index) in glyphs.peeking_take_while(|&(_, c, _)| !c.is_whitespace()) {
                     glyph_count += 1;
                     word_advance += glyph.advance.x;
-                    glyph_items.push(GlyphItem::Glyph(glyph));
+                    glyph_items.push(GlyphItem::Glyph(glyph, style_index));
                 }
                 // If there are glyphs to add, insert a `Word` and increment the advances.
                 if glyph_count > 0 {
@@ -328,12 +426,12 @@ impl GlyphIter {
                     }}
                 }
 
-                for (glyph, c) in glyphs.peeking_take_while(|&(_, c)| c.is_whitespace()) {
+                for (glyph, c, style_index) in glyphs.peeking_take_while(|&(_, c, _)| c.is_whitespace()) {
                     match c == '\t' {
                         false => {
                             whitespace_glyph_count += 1;
                             segment_item_count += 1;
-                            glyph_items.push(GlyphItem::WhitespaceGlyph(glyph));
+                            glyph_items.push(GlyphItem::WhitespaceGlyph(glyph, style_index));
                             whitespace_advance += glyph.advance.x
                         },
                         // If the whitespace is a tab, push all the accumulated whitespace, begin a
@@ -349,7 +447,7 @@ impl GlyphIter {
                                 Some(&GlyphItem::Tab{..}) => (),
                                 _ => glyph_items.insert(run_insert_index, GlyphItem::Run(run.append_run(segment_run)))
                             }
-                            glyph_items.push(GlyphItem::Tab{ str_index: glyph.str_index });
+                            glyph_items.push(GlyphItem::Tab{ str_index: glyph.str_index, style_index });
                             run = Run::default();
                             segment_run = Run::default();
                             run_insert_index = glyph_items.len();
@@ -396,8 +494,6 @@ impl GlyphIter {
                 }
 
             }
-
-            segment_index += 1;
         }
 
         if run != Run::default() {
@@ -408,11 +504,7 @@ impl GlyphIter {
             num_lines += 1;
         }
 
-        let face_size = FaceSize::new(text_style.face_size, text_style.face_size);
-
-        let font_metrics = face.metrics_sized(face_size, dpi).unwrap();
-        let line_height = (font_metrics.height / 64) as i32;
-        let (ascender, descender) = ((font_metrics.ascender / 64) as i32, (font_metrics.descender / 64) as i32);
+        let LineMetrics{ line_height, ascender, descender } = line_metrics;
 
         let v_advance = match text_style.justify.y {
             Align::Stretch => (rect.height() / (num_lines + 1)) as i32,
@@ -475,19 +567,21 @@ impl Iterator for GlyphIter {
     fn next(&mut self) -> Option<RenderGlyph> {
         loop {
             match self.glyph_items.next()? {
-                GlyphItem::Glyph(glyph) => {
+                GlyphItem::Glyph(glyph, style_index) => {
                     let render_glyph = RenderGlyph {
                         pos: Point2::from_vec(self.cursor),
                         highlight_rect: self.highlight_rect(Point2::from_vec(self.cursor), glyph.advance.x),
                         str_index: glyph.str_index,
-                        glyph_index: Some(glyph.glyph_index)
+                        glyph_index: Some(glyph.glyph_index),
+                        style_index,
+                        rtl: false
                     };
 
                     self.cursor += glyph.advance.mul_element_wise(Vector2::new(1, -1));
                     return Some(render_glyph);
                 },
                 GlyphItem::Word{..} => continue,
-                GlyphItem::WhitespaceGlyph(glyph) => {
+                GlyphItem::WhitespaceGlyph(glyph, style_index) => {
                     let cursor_advance = match self.x_justify == Align::Stretch && !self.on_hard_break {
                         false => glyph.advance.x,
                         true => {
@@ -509,7 +603,9 @@ impl Iterator for GlyphIter {
                         pos: Point2::from_vec(self.cursor),
                         highlight_rect: self.highlight_rect(Point2::from_vec(self.cursor), cursor_advance),
                         str_index: glyph.str_index,
-                        glyph_index: None
+                        glyph_index: None,
+                        style_index,
+                        rtl: false
                     };
                     self.cursor.x += cursor_advance;
 
@@ -531,13 +627,15 @@ impl Iterator for GlyphIter {
                     self.run_start_x = self.cursor.x;
                     continue;
                 },
-                GlyphItem::Tab{str_index} => {
+                GlyphItem::Tab{str_index, style_index} => {
                     let new_cursor_x = (((self.cursor.x - self.line_start_x)/self.tab_advance) + 1) * self.tab_advance + self.line_start_x;
                     let render_glyph = RenderGlyph {
                         pos: Point2::from_vec(self.cursor),
                         highlight_rect: self.highlight_rect(Point2::from_vec(self.cursor), new_cursor_x - self.cursor.x),
                         str_index,
-                        glyph_index: None
+                        glyph_index: None,
+                        style_index,
+                        rtl: false
                     };
                     self.cursor.x = new_cursor_x;
 
@@ -549,14 +647,16 @@ impl Iterator for GlyphIter {
 }
 
 impl<'a> Iterator for TextTranslate<'a> {
-    type Item = GLVertex;
+    type Item = (usize, GLVertex);
 
-    fn next(&mut self) -> Option<GLVertex> {
+    fn next(&mut self) -> Option<(usize, GLVertex)> {
         loop {
-            fn next_in_iter(i: Option<impl Iterator<Item=GLVertex>>) -> Option<GLVertex> {i.map(|mut v| v.next()).unwrap_or(None)}
+            fn next_in_iter(i: Option<impl Iterator<Item=(usize, GLVertex)>>) -> Option<(usize, GLVertex)> {i.map(|mut v| v.next()).unwrap_or(None)}
             let next_vertex =
                 next_in_iter(self.highlight_vertex_iter.as_mut())
                     .or_else(|| next_in_iter(self.glyph_vertex_iter.as_mut()))
+                    .or_else(|| next_in_iter(self.underline_vertex_iter.as_mut()))
+                    .or_else(|| next_in_iter(self.strikethrough_vertex_iter.as_mut()))
                     .or_else(|| next_in_iter(self.cursor_vertex_iter.as_mut()));
             match next_vertex {
                 Some(vert) => return Some(vert),
@@ -565,6 +665,7 @@ impl<'a> Iterator for TextTranslate<'a> {
                         ref glyph_slice,
                         ref mut glyph_slice_index,
                         ref highlight_range,
+                        ref underline_range,
                         ref mut cursor_pos,
                         ref mut glyph_draw,
                         offset,
@@ -572,20 +673,21 @@ impl<'a> Iterator for TextTranslate<'a> {
                         font_ascender,
                         font_descender,
                         ref mut glyph_vertex_iter,
+                        ref mut underline_vertex_iter,
+                        ref mut strikethrough_vertex_iter,
                         ref mut highlight_vertex_iter,
                         ref mut cursor_vertex_iter,
                     } = *self;
                     macro_rules! get_glyph_slice {
-                        (range $i:expr) => {{glyph_slice.get($i).iter().flat_map(|g| g.iter()).cloned().map(|g| g.offset(offset))}};
                         ($i:expr) => {{glyph_slice.get($i).cloned().map(|g| g.offset(offset))}};
                     }
                     let next_glyph_opt = get_glyph_slice!(*glyph_slice_index);
 
                     *cursor_vertex_iter = cursor_pos.and_then(|pos| {
                         let str_index = next_glyph_opt.map(|g| g.str_index).unwrap_or(0);
-                        let highlight_rect_opt = next_glyph_opt.map(|g| g.highlight_rect + glyph_draw.rect.min().to_vec());
+                        let glyph_offset = glyph_draw.rect.min().to_vec();
                         let base_pos = if pos == str_index {
-                            highlight_rect_opt.map(|r| r.min()).or(Some(
+                            next_glyph_opt.map(|g| Point2::new(g.cursor_x(), g.highlight_rect.min.y) + glyph_offset).or(Some(
                                 Point2 {
                                     x: match glyph_draw.text_style.justify.x {
                                         Align::Start |
@@ -602,15 +704,20 @@ impl<'a> Iterator for TextTranslate<'a> {
                                 } + glyph_draw.rect.min().to_vec()
                             ))
                         } else if pos == str_index + 1 && pos == string_len {
-                            highlight_rect_opt.map(|r| Point2::new(r.max().x, r.min().y))
+                            // The cursor's after the last glyph, which is on the opposite side of the
+                            // glyph from where the cursor's drawn before it.
+                            next_glyph_opt.map(|g| Point2::new(
+                                g.highlight_rect.min.x + g.highlight_rect.max.x - g.cursor_x(),
+                                g.highlight_rect.min.y
+                            ) + glyph_offset)
                         } else {None};
 
                         base_pos.map(|pos| {
                             *cursor_pos = None;
                             ImageTranslate::new(
                                 BoundBox::new(pos, pos + Vector2::new(1, font_ascender - font_descender)),
-                                glyph_draw.rect,
-                                glyph_draw.atlas.white().cast().unwrap_or(OffsetBox::new2(0, 0, 0, 0)),
+                                glyph_draw.clip_rect,
+                                glyph_draw.atlas.white(),
                                 glyph_draw.text_style.color,
                                 RescaleRules::StretchOnPixelCenter
                             )
@@ -629,47 +736,53 @@ impl<'a> Iterator for TextTranslate<'a> {
                         glyph_draw.glyph_atlas_image(
                             next_glyph.pos,
                             glyph_index,
+                            next_glyph.style_index,
                             is_highlighted,
                             glyph_draw.rect
                         )
                     );
 
-
-                    let starts_highlight_rect =
+                    let (underline, strikethrough, style_color) = {
+                        let style = &glyph_draw.styles[next_glyph.style_index];
                         (
-                            highlight_range.start == next_glyph.str_index &&
-                            highlight_range.len() > 0
-                        ) ||
-                        (
-                            is_highlighted &&
-                            Some(next_glyph.pos.y) != get_glyph_slice!(*glyph_slice_index - 2).map(|g| g.pos.y)
-                        );
-                    *highlight_vertex_iter = match starts_highlight_rect {
-                        true => {
-                            let mut dummy_last_glyph = get_glyph_slice!(self.glyph_slice.len() - 1).unwrap();
-                            dummy_last_glyph.pos.x += dummy_last_glyph.highlight_rect.width();
-                            dummy_last_glyph.highlight_rect.min.x += dummy_last_glyph.highlight_rect.width();
-                            dummy_last_glyph.highlight_rect.max.x = dummy_last_glyph.highlight_rect.min.x;
-                            dummy_last_glyph.str_index += 1;
-
-                            let highlight_rect_end = get_glyph_slice!(range *glyph_slice_index..)
-                                .chain(Some(dummy_last_glyph))
-                                .take_while(|g| g.pos.y == next_glyph.pos.y)
-                                .take_while(|g| g.str_index <= highlight_range.end)
-                                .last().unwrap().pos.x;
-
-                            let mut highlight_rect = next_glyph.highlight_rect;
-                            highlight_rect.max.x = highlight_rect_end;
-                            highlight_rect = highlight_rect + glyph_draw.rect.min().to_vec();
-
-                            Some(ImageTranslate::new(
-                                highlight_rect,
-                                glyph_draw.rect,
-                                glyph_draw.atlas.white().cast().unwrap_or(OffsetBox::new2(0, 0, 0, 0)),
-                                glyph_draw.text_style.highlight_bg_color,
-                                RescaleRules::StretchOnPixelCenter
-                            ))
-                        },
+                            style.underline || underline_range.contains(next_glyph.str_index),
+                            style.strikethrough,
+                            style.color
+                        )
+                    };
+                    let mut decoration_image = |y: i32| {
+                        let line_rect = BoundBox::new2(
+                            next_glyph.highlight_rect.min.x, y,
+                            next_glyph.highlight_rect.max.x, y + 1
+                        ) + glyph_draw.rect.min().to_vec();
+                        ImageTranslate::new(
+                            line_rect,
+                            glyph_draw.clip_rect,
+                            glyph_draw.atlas.white(),
+                            style_color,
+                            RescaleRules::StretchOnPixelCenter
+                        )
+                    };
+                    *underline_vertex_iter = match underline {
+                        true => Some(decoration_image(next_glyph.pos.y + 1)),
+                        false => None
+                    };
+                    *strikethrough_vertex_iter = match strikethrough {
+                        true => Some(decoration_image(next_glyph.pos.y - font_ascender / 3)),
+                        false => None
+                    };
+
+
+                    // Each glyph draws its own piece of the highlight, since bidirectional text can split
+                    // a highlighted range into several visual runs.
+                    *highlight_vertex_iter = match is_highlighted {
+                        true => Some(ImageTranslate::new(
+                            next_glyph.highlight_rect + glyph_draw.rect.min().to_vec(),
+                            glyph_draw.clip_rect,
+                            glyph_draw.atlas.white(),
+                            glyph_draw.text_style.highlight_bg_color,
+                            RescaleRules::StretchOnPixelCenter
+                        )),
                         false => None
                     };
 
@@ -712,30 +825,33 @@ impl Run {
 }
 
 impl<'a> GlyphDraw<'a> {
-    fn glyph_atlas_image(&mut self, mut glyph_pos: Point2<i32>, glyph_index: u32, is_highlighted: bool, rect: BoundBox<Point2<i32>>) -> ImageTranslate {
+    fn glyph_atlas_image(&mut self, mut glyph_pos: Point2<i32>, glyph_index: u32, style_index: usize, is_highlighted: bool, rect: BoundBox<Point2<i32>>) -> ImageTranslate {
         let GlyphDraw {
-            ref mut face,
+            ref mut font_cache,
             ref mut atlas,
             ref text_style,
+            styles,
             dpi,
             ..
         } = *self;
+        let style = &styles[style_index];
 
-        let face_size = FaceSize::new(text_style.face_size, text_style.face_size);
+        let face_size = FaceSize::new(style.face_size, style.face_size);
 
         let render_mode = RenderMode::Normal;
-        let (atlas_rect, glyph_bearing) = atlas.glyph_rect(
-            text_style.face.clone(),
-            text_style.face_size,
+        let (atlas_rect, glyph_bearing, is_colored) = atlas.glyph_rect(
+            style.face.clone(),
+            style.face_size,
             glyph_index,
             || {
-                let glyph_res = face.load_glyph(
+                let glyph_res = font_cache.face(style.face.clone()).and_then(|face| face.load_glyph(
                     glyph_index,
                     face_size,
                     dpi,
-                    LoadFlags::empty(),
+                    // Load color bitmaps, for fonts with color emoji.
+                    LoadFlags::COLOR,
                     render_mode
-                ).and_then(|mut glyph_slot| Ok((
+                )).and_then(|mut glyph_slot| Ok((
                     glyph_slot.render_glyph(render_mode)?,
                     glyph_slot.metrics()
                 )));
@@ -747,16 +863,31 @@ impl<'a> GlyphDraw<'a> {
                             0 => (&[][..], 1, DimsBox::new2(0, 0)),
                             _ => (bitmap.buffer, bitmap.pitch as usize, bitmap.dims)
                         };
+                        let is_colored = bitmap.pixel_mode == PixelMode::Bgra;
+                        let bytes_per_pixel = match is_colored {
+                            true => 4,
+                            false => 1
+                        };
                         (
                             bytes.chunks(pitch)
                                 .map(move |b|
-                                    Nu8::slice_from_raw(&b[..dims.width() as usize])
+                                    b[..dims.width() as usize * bytes_per_pixel].chunks(bytes_per_pixel).map(|p| match *p {
+                                        // Color bitmaps are stored as premultiplied BGRA.
+                                        [b, g, r, a] => {
+                                            let unmultiply = |c: u8| match a {
+                                                0 => 0,
+                                                _ => (c as u32 * 255 / a as u32) as u8
+                                            };
+                                            Rgba::new(Nu8(unmultiply(r)), Nu8(unmultiply(g)), Nu8(unmultiply(b)), Nu8(a))
+                                        },
                                         // We upload white glyphs to the atlas, which are colored by
                                         // vertex colors.
-                                        .into_iter().map(|t| Rgba::new(Nu8(255), Nu8(255), Nu8(255), *t))
+                                        _ => Rgba::new(Nu8(255), Nu8(255), Nu8(255), Nu8(p[0]))
+                                    })
                                 ),
                             bitmap.dims,
-                            glyph_metrics.hori_bearing / 64
+                            glyph_metrics.hori_bearing / 64,
+                            is_colored
                         )
                     },
                     Err(_) => {
@@ -776,17 +907,19 @@ impl<'a> GlyphDraw<'a> {
         let glyph_rect = BoundBox::new2(
             glyph_pos.x,
             glyph_pos.y,
-            glyph_pos.x + atlas_rect.width() as i32,
-            glyph_pos.y + atlas_rect.height() as i32
+            glyph_pos.x + atlas_rect.rect.width() as i32,
+            glyph_pos.y + atlas_rect.rect.height() as i32
         );
 
         ImageTranslate::new(
             glyph_rect,
-            self.rect,
-            atlas_rect.cast::<u16>().unwrap_or(OffsetBox::new2(0, 0, 0, 0)),
-            match is_highlighted {
-                false => text_style.color,
-                true => text_style.highlight_text_color
+            self.clip_rect,
+            atlas_rect,
+            match (is_colored, is_highlighted) {
+                // Colored glyphs are drawn with their own colors.
+                (true, _) => Rgba::new(Nu8(255), Nu8(255), Nu8(255), Nu8(255)),
+                (false, false) => style.color,
+                (false, true) => text_style.highlight_text_color
             },
             RescaleRules::Stretch
         )
@@ -799,11 +932,27 @@ impl RenderString {
         RenderString {
             offset: Vector2::new(0, 0),
             string,
+            spans: Vec::new(),
+            preedit: None,
             min_size: DimsBox::new2(0, 0),
             draw_data: None
         }
     }
 
+    /// Get the styled spans drawn over the string's default style.
+    #[inline]
+    pub fn spans(&self) -> &[TextSpan] {
+        &self.spans
+    }
+
+    /// Mutably access the string's spans. Where spans overlap, later spans take precedence.
+    ///
+    /// Span ranges aren't adjusted when the string is edited.
+    #[inline]
+    pub fn spans_mut(&mut self) -> &mut Vec<TextSpan> {
+        &mut self.spans
+    }
+
     #[inline]
     pub fn string(&self) -> &str {
         &self.string
@@ -811,9 +960,6 @@ impl RenderString {
 
     #[inline]
     pub fn string_mut(&mut self) -> &mut String {
-        if let Some(ref mut draw_data) = self.draw_data {
-            draw_data.shaped_glyphs.clear();
-        }
         &mut self.string
     }
 
@@ -822,33 +968,143 @@ impl RenderString {
         self.min_size
     }
 
-    fn reshape_glyphs<'a, F>(&mut self,
+    /// Lay out the string without drawing it, and get the size of the area the text covers. The
+    /// text gets wrapped at `max_width`, if the style allows wrapping.
+    pub(in gl_render) fn measure(
+        &self,
+        max_width: Option<i32>,
+        text_style: &ThemeText,
+        font_cache: &mut FontCache,
+        shaper: &mut Shaper,
+        shaped_text: &mut ShapedBuffer,
+        dpi: DPI
+    ) -> DimsBox<Point2<i32>>
+    {
+        // Large enough for any text, but small enough that positioning glyphs within it won't overflow.
+        const MAX_DIM: i32 = 1 << 24;
+        let rect = BoundBox::new2(0, 0, max_width.unwrap_or(MAX_DIM), MAX_DIM);
+
+        let mut glyphs = Vec::new();
+        layout_string(&self.string, &self.spans, rect, text_style, font_cache, shaper, shaped_text, dpi, None, &mut glyphs);
+
+        let bounds = glyphs.iter().map(|g| g.highlight_rect).fold(None, |bounds: Option<BoundBox<Point2<i32>>>, rect| match bounds {
+            Some(bounds) => Some(BoundBox::new2(
+                cmp::min(bounds.min.x, rect.min.x), cmp::min(bounds.min.y, rect.min.y),
+                cmp::max(bounds.max.x, rect.max.x), cmp::max(bounds.max.y, rect.max.y)
+            )),
+            None => Some(rect)
+        });
+        match bounds {
+            // withholding the +1 leads to clipping bugs, as with `min_size`.
+            Some(bounds) => DimsBox::new2(bounds.width() + 1, bounds.height()),
+            None => DimsBox::new2(0, 0)
+        }
+    }
+
+    /// Get the grapheme boundaries the cursor can be placed at, in the order they're drawn: line by
+    /// line, and from left to right within each line. Boundaries drawn at the same position keep
+    /// their glyphs' visual order.
+    ///
+    /// Returns an empty list if the string hasn't been drawn since it was last modified.
+    fn visual_cursor_stops(&self) -> Vec<usize> {
+        let layout_len = self.layout_len();
+        let is_grapheme_boundary = {
+            let boundaries: Vec<usize> = self.string.grapheme_indices(true).map(|(i, _)| i).chain(Some(self.string.len())).collect();
+            move |index: usize| boundaries.binary_search(&index).is_ok()
+        };
+
+        let mut has_stop = vec![false; self.string.len() + 1];
+        let mut stops = Vec::new();
+        for glyph in self.glyph_iter().filter(|g| g.str_index <= layout_len) {
+            // Only the first glyph drawn for each boundary places the cursor, so the rest get skipped.
+            let index = self.string_index(glyph.str_index);
+            if !has_stop[index] && is_grapheme_boundary(index) {
+                has_stop[index] = true;
+                stops.push((glyph.pos.y, glyph.cursor_x(), index));
+            }
+        }
+        // The sort is stable, so stops at the same position stay in visual order.
+        stops.sort_by_key(|&(y, x, _)| (y, x));
+        stops.into_iter().map(|(_, _, index)| index).collect()
+    }
+
+    /// Get the byte index of the grapheme drawn under the given point, or `None` if no grapheme
+    /// was drawn there. Always returns `None` if the string hasn't been drawn yet.
+    pub fn str_index_at(&self, pos: Point2<i32>) -> Option<usize> {
+        let layout_len = self.layout_len();
+        self.glyph_iter()
+            .filter(|glyph| glyph.str_index < layout_len)
+            .find(|glyph| glyph.highlight_rect.contains(pos))
+            .map(|glyph| self.string_index(glyph.str_index))
+    }
+
+    /// Get the text that gets laid out: the string, with any composition text drawn in it.
+    fn layout_text(&self) -> Cow<str> {
+        layout_text(&self.string, &self.preedit)
+    }
+
+    fn layout_len(&self) -> usize {
+        match self.preedit {
+            Some((ref range, ref text)) if valid_preedit_range(&self.string, range) =>
+                self.string.len() - range.len() + text.len(),
+            _ => self.string.len()
+        }
+    }
+
+    /// Convert a byte index in the laid out text to a byte index in the string. Indices within the
+    /// composition text get moved to the position the composition text is drawn at.
+    fn string_index(&self, layout_index: usize) -> usize {
+        match self.preedit {
+            Some((ref range, ref text)) if valid_preedit_range(&self.string, range) && range.start <= layout_index =>
+                match layout_index < range.start + text.len() {
+                    true => range.start,
+                    false => layout_index - text.len() + range.len()
+                },
+            _ => layout_index
+        }
+    }
+
+    fn reshape_glyphs(&mut self,
         rect: BoundBox<Point2<i32>>,
-        shape_text: F,
         text_style: &ThemeText,
-        face: &mut Face<()>,
+        font_cache: &mut FontCache,
+        shaper: &mut Shaper,
+        shaped_text: &mut ShapedBuffer,
         dpi: DPI,
+        mask: Option<char>,
         cursor_pos_opt: Option<usize>
-    ) -> &[RenderGlyph]
-        where F: FnOnce(&str, &mut Face<()>) -> &'a ShapedBuffer
+    ) -> (&[RenderGlyph], &[RunStyle])
     {
+        let text = layout_text(&self.string, &self.preedit);
         let use_cached_glyphs: bool;
         match self.draw_data {
             Some(ref mut draw_data) => {
+                // Comparing the string is much cheaper than running it through the shaper again,
+                // so the glyphs get reused whenever the string is unchanged, even if it's been
+                // mutably accessed since the last draw.
                 use_cached_glyphs =
-                    draw_data.shaped_glyphs.len() != 0 &&
-                    (text_style, dpi, rect) ==
-                    (&draw_data.text_style, draw_data.dpi, draw_data.draw_rect);
-
-                // Update draw_data contents to reflect new values
-                draw_data.text_style = text_style.clone();
-                draw_data.dpi = dpi;
-                draw_data.draw_rect = rect;
+                    (&*text, &self.spans, mask, text_style, dpi, rect) ==
+                    (&*draw_data.string, &draw_data.spans, draw_data.mask, &draw_data.text_style, draw_data.dpi, draw_data.draw_rect);
+
+                if !use_cached_glyphs {
+                    // Update draw_data contents to reflect new values
+                    draw_data.string.clear();
+                    draw_data.string.push_str(&text);
+                    draw_data.spans.clone_from(&self.spans);
+                    draw_data.mask = mask;
+                    draw_data.text_style = text_style.clone();
+                    draw_data.dpi = dpi;
+                    draw_data.draw_rect = rect;
+                }
             },
             None => {
                 use_cached_glyphs = false;
                 self.draw_data = Some(StringDrawData {
                     shaped_glyphs: Vec::new(),
+                    styles: Vec::new(),
+                    string: text.to_string(),
+                    spans: self.spans.clone(),
+                    mask,
                     text_style: text_style.clone(),
                     dpi,
                     draw_rect: rect,
@@ -858,20 +1114,13 @@ impl RenderString {
 
         let draw_data = self.draw_data.as_mut().unwrap();
         if !use_cached_glyphs {
-            let shaped_buffer = shape_text(&self.string, face);
-            draw_data.shaped_glyphs.clear();
-
-            let mut glyph_iter = GlyphIter::new(rect, shaped_buffer, text_style, face, dpi);
-            draw_data.shaped_glyphs.extend(&mut glyph_iter);
-
-            self.min_size = match text_style.line_wrap {
-                LineWrap::None => DimsBox::new2(
-                    // withholding the +1 leads to clipping bugs so I'm just including it
-                    glyph_iter.cursor.x - glyph_iter.line_start_x + 1,
-                    glyph_iter.line_height + -glyph_iter.font_descender
-                ),
-                _ => DimsBox::new2(0, 0)
-            };
+            let (styles, min_size) = layout_string(
+                &text, &self.spans, rect, text_style,
+                font_cache, shaper, shaped_text, dpi, mask,
+                &mut draw_data.shaped_glyphs
+            );
+            draw_data.styles = styles;
+            self.min_size = min_size;
         }
 
         if let Some(cursor_pos) = cursor_pos_opt {
@@ -903,7 +1152,13 @@ impl RenderString {
             self.offset += offset;
         }
 
-        &self.draw_data.as_ref().unwrap().shaped_glyphs[..]
+        let draw_data = self.draw_data.as_ref().unwrap();
+        (&draw_data.shaped_glyphs[..], &draw_data.styles[..])
+    }
+
+    /// Get the draw data, if its glyphs were laid out from the string's current contents.
+    fn current_draw_data(&self) -> Option<&StringDrawData> {
+        self.draw_data.as_ref().filter(|draw_data| draw_data.string == self.layout_text() && draw_data.spans == self.spans)
     }
 
     fn glyph_iter<'a>(&'a self) -> impl 'a + Iterator<Item=RenderGlyph> + DoubleEndedIterator {
@@ -911,21 +1166,33 @@ impl RenderString {
         let offset_glyph = move |g: RenderGlyph| g.offset(glyph_offset);
         let empty_iter = [].iter().cloned().chain(None).map(offset_glyph.clone());
 
-        let shaped_glyphs = match self.draw_data {
-            Some(ref draw_data) => &draw_data.shaped_glyphs,
+        let shaped_glyphs = match self.current_draw_data() {
+            Some(draw_data) => &draw_data.shaped_glyphs,
             None => return empty_iter
         };
 
         if let Some(last_glyph) = shaped_glyphs.last().cloned() {
-            let dummy_last_glyph_pos_x = last_glyph.pos.x + last_glyph.highlight_rect.width();
+            // The dummy glyph goes after the logically last glyph, which is on the last line, but
+            // isn't necessarily drawn last if the line has been reordered.
+            let last_line_y = last_glyph.pos.y;
+            let last_glyph = shaped_glyphs.iter().rev()
+                .take_while(|g| g.pos.y == last_line_y)
+                .max_by_key(|g| g.str_index)
+                .cloned().unwrap_or(last_glyph);
+            let dummy_last_glyph_pos_x = match last_glyph.rtl {
+                false => last_glyph.highlight_rect.max.x,
+                true => last_glyph.highlight_rect.min.x
+            };
             let dummy_last_glyph = RenderGlyph {
                 pos: Point2::new(dummy_last_glyph_pos_x, last_glyph.pos.y),
                 highlight_rect: BoundBox::new2(
                     dummy_last_glyph_pos_x, last_glyph.highlight_rect.min.y,
                     dummy_last_glyph_pos_x, last_glyph.highlight_rect.min.y + last_glyph.highlight_rect.height()
                 ),
-                str_index: self.string.len(),
-                glyph_index: None
+                str_index: self.layout_len(),
+                glyph_index: None,
+                style_index: 0,
+                rtl: last_glyph.rtl
             };
             shaped_glyphs.iter().cloned().chain(Some(dummy_last_glyph)).map(offset_glyph)
         } else {
@@ -939,9 +1206,15 @@ impl EditString {
         EditString {
             render_string,
             draw_cursor: false,
+            mask: None,
+            max_len: None,
             cursor_pos: 0,
             highlight_range: 0..0,
-            cursor_target_x_px: None
+            preedit_cursor: None,
+            cursor_target_x_px: None,
+            undo_stack: VecDeque::new(),
+            redo_stack: Vec::new(),
+            last_edit: None
         }
     }
 
@@ -953,6 +1226,7 @@ impl EditString {
     #[inline]
     pub fn cursor_pos_mut(&mut self) -> &mut usize {
         self.cursor_target_x_px = None;
+        self.last_edit = None;
         &mut self.cursor_pos
     }
 
@@ -961,8 +1235,41 @@ impl EditString {
         self.highlight_range.clone()
     }
 
+    /// Get the character drawn in place of each grapheme, if the string is masked.
+    #[inline]
+    pub fn mask(&self) -> Option<char> {
+        self.mask
+    }
+
+    /// Draw `mask` in place of each grapheme in the string. If `None`, the string is drawn as-is.
+    pub fn set_mask(&mut self, mask: Option<char>) {
+        self.mask = mask;
+    }
+
+    /// Get the maximum number of graphemes that can be inserted into the string.
+    #[inline]
+    pub fn max_len(&self) -> Option<usize> {
+        self.max_len
+    }
+
+    /// Set the maximum number of graphemes that can be inserted into the string. This is only
+    /// enforced when inserting text, so a string that's already longer won't be truncated.
+    #[inline]
+    pub fn set_max_len(&mut self, max_len: Option<usize>) {
+        self.max_len = max_len;
+    }
+
+    /// The number of graphemes that can still be inserted before hitting `max_len`.
+    fn remaining_len(&self) -> usize {
+        match self.max_len {
+            Some(max_len) => max_len.saturating_sub(self.render_string.string.graphemes(true).count()),
+            None => usize::max_value()
+        }
+    }
+
     pub fn move_cursor_vertical(&mut self, dist: isize, expand_selection: bool) {
         let cursor_start_pos = self.cursor_pos;
+        self.last_edit = None;
         let EditString {
             ref mut cursor_pos,
             ref mut cursor_target_x_px,
@@ -974,9 +1281,8 @@ impl EditString {
             ($iter:expr) => {{
                 let mut glyph_iter = $iter.skip_while(move |g| g.str_index != *cursor_pos);
                 if let Some(cursor_glyph) = glyph_iter.next() {
-                    let cursor_pos_px = cursor_glyph.highlight_rect.min;
                     if cursor_target_x_px.is_none() {
-                        *cursor_target_x_px = Some(cursor_pos_px.x);
+                        *cursor_target_x_px = Some(cursor_glyph.cursor_x());
                     }
                     let target_x_px = cursor_target_x_px.unwrap();
 
@@ -985,7 +1291,7 @@ impl EditString {
                     let mut line_delta = 0;
 
                     for glyph in glyph_iter {
-                        let glyph_dist_x = (target_x_px - glyph.highlight_rect.min.x).abs();
+                        let glyph_dist_x = (target_x_px - glyph.cursor_x()).abs();
                         if glyph.highlight_rect.min.y != cur_line_y {
                             line_delta += 1;
                             cur_line_y = glyph.highlight_rect.min.y;
@@ -1025,11 +1331,48 @@ impl EditString {
         }
     }
 
+    /// Move the cursor `dist` graphemes or words on screen. Positive values move the cursor right,
+    /// and negative values move it left, following the order the text was drawn in.
+    ///
+    /// Words, and strings that haven't been drawn since they were last modified, are moved through
+    /// in logical order, reversed if the cursor is in right-to-left text.
+    pub fn move_cursor_visual(&mut self, dist: isize, jump_to_word_boundaries: bool, expand_selection: bool) {
+        let collapse_selection = self.highlight_range.len() > 0 && !expand_selection;
+        let stops = match jump_to_word_boundaries || collapse_selection {
+            true => Vec::new(),
+            false => self.render_string.visual_cursor_stops()
+        };
+
+        match stops.iter().position(|&index| index == self.cursor_pos) {
+            Some(stop) => {
+                let stop = cmp::max(0, cmp::min(stop as isize + dist, stops.len() as isize - 1));
+                self.move_cursor_to(stops[stop as usize], expand_selection);
+            },
+            None => {
+                let cursor_pos = self.cursor_pos;
+                let cursor_rtl = self.render_string.glyph_iter()
+                    .find(|g| g.str_index == cursor_pos)
+                    .map(|g| g.rtl).unwrap_or(false);
+                let dist = match cursor_rtl {
+                    true => -dist,
+                    false => dist
+                };
+                self.move_cursor_horizontal(dist, jump_to_word_boundaries, expand_selection);
+            }
+        }
+    }
+
+    /// Move the cursor `dist` graphemes or words through the string. Positive values move the
+    /// cursor towards the end of the string, and negative values move it towards the start.
     pub fn move_cursor_horizontal(&mut self, dist: isize, jump_to_word_boundaries: bool, expand_selection: bool) {
         let cursor_start_pos = self.cursor_pos;
         self.cursor_target_x_px = None;
+        self.last_edit = None;
         self.cursor_pos = match (self.highlight_range.len() * !expand_selection as usize, dist.signum(), jump_to_word_boundaries) {
             (_, 0, _) => return,
+            // Word boundaries would reveal the structure of masked text, so treat it as one word.
+            (0, 1, true) if self.mask.is_some() => self.render_string.string.len(),
+            (0, -1, true) if self.mask.is_some() => 0,
             (0, 1, false) =>
                 self.render_string.string[self.cursor_pos..].grapheme_indices(true)
                     .skip(dist as usize).map(|(i, _)| i + self.cursor_pos)
@@ -1058,6 +1401,100 @@ impl EditString {
         }
     }
 
+    /// Move the cursor to the start (if `dir` is negative) or end (if `dir` is positive) of the line
+    /// the cursor is drawn on.
+    pub fn move_cursor_line_boundary(&mut self, dir: isize, expand_selection: bool) {
+        let line_range = self.line_range(self.cursor_pos);
+        match dir.signum() {
+            1 => {
+                // A cursor at the end of a wrapped line gets drawn at the start of the next line,
+                // so place it before the line's last grapheme instead.
+                let line_end = match line_range.start < line_range.end && self.line_range(line_range.end).start == line_range.end {
+                    true => self.render_string.string[..line_range.end].grapheme_indices(true)
+                        .next_back().map(|(i, _)| i).unwrap_or(line_range.start),
+                    false => line_range.end
+                };
+                self.move_cursor_to(line_end, expand_selection)
+            },
+            -1 => self.move_cursor_to(line_range.start, expand_selection),
+            _ => ()
+        }
+    }
+
+    /// Move the cursor to the start (if `dir` is negative) or end (if `dir` is positive) of the
+    /// string.
+    pub fn move_cursor_string_boundary(&mut self, dir: isize, expand_selection: bool) {
+        let string_len = self.render_string.string.len();
+        match dir.signum() {
+            1 => self.move_cursor_to(string_len, expand_selection),
+            -1 => self.move_cursor_to(0, expand_selection),
+            _ => ()
+        }
+    }
+
+    fn move_cursor_to(&mut self, pos: usize, expand_selection: bool) {
+        let cursor_start_pos = self.cursor_pos;
+        self.cursor_target_x_px = None;
+        self.last_edit = None;
+        self.cursor_pos = pos;
+        if expand_selection {
+            self.expand_selection_to_cursor(cursor_start_pos);
+        } else {
+            self.highlight_range = 0..0;
+        }
+    }
+
+    /// Get the range of the laid-out line containing `index`, not including the line's trailing
+    /// line break. If the string hasn't been drawn since it was last modified, this falls back to
+    /// the range between the line breaks surrounding `index`.
+    fn line_range(&self, index: usize) -> Range<usize> {
+        let render_string = &self.render_string;
+        let string = &render_string.string;
+        let is_line_break = |g: &str| g == "\n" || g == "\r" || g == "\r\n";
+
+        let layout_len = render_string.layout_len();
+        let glyphs: Vec<(i32, usize)> = render_string.glyph_iter()
+            .filter(|g| g.str_index <= layout_len)
+            .map(|g| (g.pos.y, render_string.string_index(g.str_index)))
+            .collect();
+        // The line of the glyph starting at `index`, or of the closest glyph before it.
+        let line_y = glyphs.iter()
+            .filter(|&&(_, i)| i <= index)
+            .min_by_key(|&&(_, i)| index - i)
+            .map(|&(y, _)| y);
+
+        if let Some(line_y) = line_y {
+            let (start, last) = glyphs.iter()
+                .filter(|&&(y, _)| y == line_y)
+                .fold((usize::max_value(), 0), |(start, last), &(_, i)| (cmp::min(start, i), cmp::max(last, i)));
+            let end = match string[last..].graphemes(true).next() {
+                Some(g) if !is_line_break(g) => last + g.len(),
+                _ => last
+            };
+            return start..end;
+        }
+
+        let start = string[..index].grapheme_indices(true).rev()
+            .find(|&(_, g)| is_line_break(g))
+            .map(|(i, g)| i + g.len())
+            .unwrap_or(0);
+        let end = string[index..].grapheme_indices(true)
+            .find(|&(_, g)| is_line_break(g))
+            .map(|(i, _)| i + index)
+            .unwrap_or(string.len());
+        start..end
+    }
+
+    /// Get the range of the word containing `index`, as defined by [UAX #29](https://unicode.org/reports/tr29/).
+    fn word_range(&self, index: usize) -> Range<usize> {
+        let string = &self.render_string.string;
+        string.split_word_bound_indices()
+            .map(|(i, word)| i..i + word.len())
+            .find(|word_range| word_range.contains(index))
+            .or_else(|| string.split_word_bound_indices().last().map(|(i, word)| i..i + word.len()))
+            .unwrap_or(0..0)
+    }
+
     fn expand_selection_to_cursor(&mut self, cursor_start_pos: usize) {
         if self.highlight_range.len() == 0 {
             self.highlight_range = cursor_start_pos..cursor_start_pos;
@@ -1078,8 +1515,8 @@ impl EditString {
     }
 
     pub fn select_on_line(&mut self, segment: Segment<Point2<i32>>) {
-        let shaped_glyphs = match self.render_string.draw_data {
-            Some(ref draw_data) => &draw_data.shaped_glyphs,
+        let shaped_glyphs = match self.render_string.current_draw_data() {
+            Some(draw_data) => &draw_data.shaped_glyphs,
             None => {self.highlight_range = 0..0; return}
         };
 
@@ -1104,62 +1541,195 @@ impl EditString {
             let glyph_start_y_dist = y_dist(segment.start);
             let glyph_end_x_dist = x_dist(segment.end);
             let glyph_end_y_dist = y_dist(segment.end);
-            let highlight_center = glyph.highlight_rect.center();
 
             if glyph_start_y_dist < min_start_y_dist {
                 min_start_y_dist = glyph_start_y_dist;
                 min_start_x_dist = glyph_start_x_dist;
-                start_index = glyph.str_index + (highlight_center.x <= segment.start.x) as usize;
+                start_index = glyph.str_index + glyph.after_center(segment.start.x) as usize;
             }
             if glyph_end_y_dist < min_end_y_dist {
                 min_end_y_dist = glyph_end_y_dist;
                 min_end_x_dist = glyph_end_x_dist;
-                end_index = glyph.str_index + (highlight_center.x <= segment.end.x) as usize;
+                end_index = glyph.str_index + glyph.after_center(segment.end.x) as usize;
             }
             if glyph_start_x_dist < min_start_x_dist && glyph_start_y_dist <= min_start_y_dist {
                 min_start_x_dist = glyph_start_x_dist;
-                start_index = glyph.str_index + (highlight_center.x <= segment.start.x) as usize;
+                start_index = glyph.str_index + glyph.after_center(segment.start.x) as usize;
             }
             if glyph_end_x_dist < min_end_x_dist && glyph_end_y_dist <= min_end_y_dist {
                 min_end_x_dist = glyph_end_x_dist;
-                end_index = glyph.str_index + (highlight_center.x <= segment.end.x) as usize;
+                end_index = glyph.str_index + glyph.after_center(segment.end.x) as usize;
             }
         }
 
         self.highlight_range = cmp::min(start_index, end_index)..cmp::max(start_index, end_index);
         self.cursor_pos = end_index;
+        self.last_edit = None;
+    }
+
+    /// Select the word under the given point.
+    pub fn select_word_at(&mut self, pos: Point2<i32>) {
+        self.select_on_line(Segment::new(pos, pos));
+        if self.mask.is_some() {
+            // Masked text is treated as a single word.
+            self.select_all();
+            return;
+        }
+        self.highlight_range = self.word_range(self.cursor_pos);
+        self.cursor_pos = self.highlight_range.end;
+    }
+
+    /// Select the laid-out line under the given point.
+    pub fn select_line_at(&mut self, pos: Point2<i32>) {
+        self.select_on_line(Segment::new(pos, pos));
+        self.highlight_range = self.line_range(self.cursor_pos);
+        self.cursor_pos = self.highlight_range.end;
     }
 
     pub fn select_all(&mut self) {
         self.highlight_range = 0..self.render_string.string.len();
         self.cursor_pos = self.highlight_range.end;
+        self.last_edit = None;
     }
 
     pub fn deselect_all(&mut self) {
         self.highlight_range = 0..0;
+        self.last_edit = None;
     }
 
-    pub fn insert_char(&mut self, c: char) {
-        if self.highlight_range.len() != 0 {
-            self.render_string.string_mut().drain(self.highlight_range.clone());
-            self.cursor_pos = self.highlight_range.start;
-            self.highlight_range = 0..0;
+    /// Revert the most recent edit. Returns `true` if there was an edit to undo.
+    pub fn undo(&mut self) -> bool {
+        match self.undo_stack.pop_back() {
+            Some(state) => {
+                let redo_state = self.edit_state();
+                self.redo_stack.push(redo_state);
+                self.restore_state(state);
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Re-apply the most recently undone edit. Returns `true` if there was an edit to redo.
+    pub fn redo(&mut self) -> bool {
+        match self.redo_stack.pop() {
+            Some(state) => {
+                let undo_state = self.edit_state();
+                self.undo_stack.push_back(undo_state);
+                self.restore_state(state);
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Throw away the undo and redo history.
+    pub fn clear_history(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.last_edit = None;
+    }
+
+    fn edit_state(&self) -> EditState {
+        EditState {
+            string: self.render_string.string.clone(),
+            cursor_pos: self.cursor_pos,
+            highlight_range: self.highlight_range.clone()
+        }
+    }
+
+    fn restore_state(&mut self, state: EditState) {
+        *self.render_string.string_mut() = state.string;
+        self.cursor_pos = state.cursor_pos;
+        self.highlight_range = state.highlight_range;
+        self.cursor_target_x_px = None;
+        self.last_edit = None;
+    }
+
+    /// Get the state to push onto the undo stack before performing an edit of the given kind, or
+    /// `None` if the edit gets merged into the previous undo entry.
+    fn undo_state_for(&self, kind: EditKind) -> Option<EditState> {
+        match (kind, self.last_edit) {
+            (EditKind::Typing, Some(EditKind::Typing)) if self.highlight_range.len() == 0 => None,
+            _ => Some(self.edit_state())
+        }
+    }
+
+    fn push_undo(&mut self, state: Option<EditState>, kind: EditKind) {
+        if let Some(state) = state {
+            self.undo_stack.push_back(state);
+            if self.undo_stack.len() > MAX_UNDO_LEN {
+                self.undo_stack.pop_front();
+            }
+        }
+        self.redo_stack.clear();
+        self.last_edit = Some(kind);
+    }
+
+    /// Insert a character at the cursor, replacing any highlighted text. Returns `true` if the
+    /// string was modified.
+    pub fn insert_char(&mut self, c: char) -> bool {
+        let kind = match self.highlight_range.len() {
+            0 => EditKind::Typing,
+            _ => EditKind::Other
+        };
+        let undo_state = self.undo_state_for(kind);
+
+        let drained_highlight = self.drain_highlight();
+        let modified = match self.remaining_len() {
+            0 => drained_highlight,
+            _ => {
+                self.render_string.string_mut().insert(self.cursor_pos, c);
+                self.cursor_pos += c.len_utf8();
+                true
+            }
+        };
+
+        if modified {
+            self.push_undo(undo_state, kind);
+        }
+        modified
+    }
+
+    /// Insert a string at the cursor, replacing any highlighted text. If inserting the full string
+    /// would exceed `max_len`, only the graphemes that fit are inserted. Returns `true` if the
+    /// string was modified.
+    pub fn insert_str(&mut self, s: &str) -> bool {
+        let undo_state = self.undo_state_for(EditKind::Other);
+
+        let drained_highlight = self.drain_highlight();
+        let s = match s.grapheme_indices(true).nth(self.remaining_len()) {
+            Some((truncate_index, _)) => &s[..truncate_index],
+            None => s
+        };
+        let modified = match s.len() {
+            0 => drained_highlight,
+            _ => {
+                self.render_string.string_mut().insert_str(self.cursor_pos, s);
+                self.cursor_pos += s.len();
+                true
+            }
+        };
+
+        if modified {
+            self.push_undo(undo_state, EditKind::Other);
         }
-        self.render_string.string_mut().insert(self.cursor_pos, c);
-        self.cursor_pos += c.len_utf8();
+        modified
     }
 
-    pub fn insert_str(&mut self, s: &str) {
+    fn drain_highlight(&mut self) -> bool {
         if self.highlight_range.len() != 0 {
             self.render_string.string_mut().drain(self.highlight_range.clone());
             self.cursor_pos = self.highlight_range.start;
             self.highlight_range = 0..0;
+            true
+        } else {
+            false
         }
-        self.render_string.string_mut().insert_str(self.cursor_pos, s);
-        self.cursor_pos += s.len();
     }
 
     pub fn delete_chars(&mut self, dist: isize, jump_to_word_boundaries: bool) {
+        let undo_state = self.undo_state_for(EditKind::Other);
         let drain_range = if self.highlight_range.len() != 0 {
             self.highlight_range.clone()
         } else {
@@ -1171,7 +1741,624 @@ impl EditString {
         self.render_string.string_mut().drain(drain_range.clone());
         self.highlight_range = 0..0;
         self.cursor_pos = drain_range.start;
+
+        if drain_range.len() != 0 {
+            self.push_undo(undo_state, EditKind::Other);
+        }
+    }
+
+    /// Get the range of the uncommitted IME composition text within the drawn text. The
+    /// composition text isn't part of the string itself.
+    #[inline]
+    pub fn preedit_range(&self) -> Range<usize> {
+        match self.render_string.preedit {
+            Some((ref range, ref text)) => range.start..range.start + text.len(),
+            None => 0..0
+        }
+    }
+
+    /// Get the uncommitted IME composition text.
+    #[inline]
+    pub fn preedit(&self) -> Option<&str> {
+        self.render_string.preedit.as_ref().map(|&(_, ref text)| &text[..])
     }
+
+    /// Replace the uncommitted IME composition text, which is drawn underlined at the cursor, in
+    /// place of any highlighted text.
+    /// `cursor` is the byte range of the IME's cursor within `text`.
+    ///
+    /// Composition text doesn't get inserted into the string until it's committed with
+    /// `insert_str`, and gets truncated to the number of graphemes that could be committed.
+    pub fn set_preedit(&mut self, text: &str, cursor: Option<(usize, usize)>) {
+        self.clear_preedit();
+
+        // Committing replaces the highlighted text, which makes room for more graphemes.
+        let highlight_len = self.render_string.string[self.highlight_range.clone()].graphemes(true).count();
+        let text = match text.grapheme_indices(true).nth(self.remaining_len().saturating_add(highlight_len)) {
+            Some((truncate_index, _)) => &text[..truncate_index],
+            None => text
+        };
+        if text.len() == 0 {
+            return;
+        }
+
+        let preedit_range = match self.highlight_range.len() {
+            0 => self.cursor_pos..self.cursor_pos,
+            _ => self.highlight_range.clone()
+        };
+        self.render_string.preedit = Some((preedit_range, text.to_string()));
+        self.preedit_cursor = cursor.map(|(start, end)| (cmp::min(start, text.len()), cmp::min(end, text.len())));
+        self.last_edit = None;
+        self.cursor_target_x_px = None;
+    }
+
+    /// Discard any uncommitted IME composition text.
+    pub fn clear_preedit(&mut self) {
+        self.render_string.preedit = None;
+        self.preedit_cursor = None;
+    }
+
+    /// Get the cursor's position within the drawn text, which includes any composition text.
+    fn layout_cursor_pos(&self) -> usize {
+        match self.render_string.preedit {
+            Some((ref range, ref text)) => range.start + self.preedit_cursor.map(|(_, end)| end).unwrap_or(text.len()),
+            None => self.cursor_pos
+        }
+    }
+
+    /// Get the range highlighted within the drawn text. While composing, this is the range the IME
+    /// has selected within the composition text.
+    fn layout_highlight_range(&self) -> Range<usize> {
+        match self.render_string.preedit {
+            Some((ref range, _)) => self.preedit_cursor.map(|(start, end)| range.start + start..range.start + end).unwrap_or(0..0),
+            None => self.highlight_range.clone()
+        }
+    }
+
+    /// Get the rectangle of the cursor, relative to the rect the string was last drawn in.
+    ///
+    /// Returns `None` if the string hasn't been drawn since it was last modified.
+    pub fn cursor_rect(&self) -> Option<BoundBox<Point2<i32>>> {
+        let draw_rect = self.render_string.draw_data.as_ref()?.draw_rect;
+        let cursor_pos = self.layout_cursor_pos();
+        let cursor_glyph = self.render_string.glyph_iter()
+            .filter(|g| cursor_pos <= g.str_index)
+            .min_by_key(|g| g.str_index)?;
+        let (cursor_x, glyph_rect) = (cursor_glyph.cursor_x(), cursor_glyph.highlight_rect);
+        Some(BoundBox::new2(cursor_x, glyph_rect.min.y, cursor_x + 1, glyph_rect.max.y) + draw_rect.min().to_vec())
+    }
+}
+
+impl RunStyle {
+    fn new(text_style: &ThemeText, span_style: &SpanStyle, font_cache: &mut FontCache) -> RunStyle {
+        let variant_face = match (span_style.bold.unwrap_or(false), span_style.italic.unwrap_or(false)) {
+            (true, true) => text_style.bold_italic_face.as_ref()
+                .or(text_style.bold_face.as_ref())
+                .or(text_style.italic_face.as_ref()),
+            (true, false) => text_style.bold_face.as_ref(),
+            (false, true) => text_style.italic_face.as_ref(),
+            (false, false) => None
+        };
+        // Fall back to the regular face if the variant can't be loaded.
+        let face = variant_face
+            .and_then(|face| font_cache.face(face.clone()).ok().map(|_| face))
+            .unwrap_or(&text_style.face);
+
+        RunStyle {
+            face: face.clone(),
+            face_size: span_style.face_size.unwrap_or(text_style.face_size),
+            color: span_style.color.unwrap_or(text_style.color),
+            underline: span_style.underline.unwrap_or(false),
+            strikethrough: span_style.strikethrough.unwrap_or(false)
+        }
+    }
+}
+
+/// Split the string into runs of uniform style. Returns the list of styles, and each run's range
+/// along with the index of its style. The first style is always the unmodified `text_style`.
+///
+/// Span bounds past the end of the string get clamped to its end, and bounds within a character
+/// get moved back to the start of the character.
+fn style_runs(
+    spans: &[TextSpan],
+    string: &str,
+    text_style: &ThemeText,
+    font_cache: &mut FontCache
+) -> (Vec<RunStyle>, Vec<(Range<usize>, usize)>)
+{
+    let mut styles = vec![RunStyle::new(text_style, &SpanStyle::default(), font_cache)];
+    let mut runs: Vec<(Range<usize>, usize)> = Vec::new();
+
+    let char_boundary = |i: usize| {
+        let mut i = cmp::min(i, string.len());
+        while !string.is_char_boundary(i) {
+            i -= 1;
+        }
+        i
+    };
+    let mut boundaries: Vec<usize> = spans.iter()
+        .flat_map(|span| Some(span.range.start).into_iter().chain(Some(span.range.end)))
+        .chain(Some(0)).chain(Some(string.len()))
+        .map(&char_boundary)
+        .collect();
+    boundaries.sort();
+    boundaries.dedup();
+
+    for (&start, &end) in boundaries.iter().tuple_windows() {
+        let mut span_style = SpanStyle::default();
+        let spans_covering = spans.iter().filter(|span| char_boundary(span.range.start) <= start && end <= char_boundary(span.range.end));
+        for span in spans_covering {
+            span_style.bold = span.style.bold.or(span_style.bold);
+            span_style.italic = span.style.italic.or(span_style.italic);
+            span_style.underline = span.style.underline.or(span_style.underline);
+            span_style.strikethrough = span.style.strikethrough.or(span_style.strikethrough);
+            span_style.color = span.style.color.or(span_style.color);
+            span_style.face_size = span.style.face_size.or(span_style.face_size);
+        }
+
+        let style = RunStyle::new(text_style, &span_style, font_cache);
+        let style_index = match styles.iter().position(|s| *s == style) {
+            Some(index) => index,
+            None => {
+                styles.push(style);
+                styles.len() - 1
+            }
+        };
+
+        match runs.last_mut() {
+            Some(&mut (ref mut run_range, run_style)) if run_style == style_index => run_range.end = end,
+            _ => runs.push((start..end, style_index))
+        }
+    }
+
+    (styles, runs)
+}
+
+/// Shape each run with its style's face, and join the shaped runs into line break segments.
+fn shape_segments(
+    string: &str,
+    runs: &[(Range<usize>, usize)],
+    styles: &mut Vec<RunStyle>,
+    primary_face: &ThemeFace,
+    font_cache: &mut FontCache,
+    shaper: &mut Shaper,
+    shaped_text: &mut ShapedBuffer,
+    dpi: DPI
+) -> Vec<StyledSegment>
+{
+    let mut segments: Vec<StyledSegment> = Vec::new();
+    let mut join_next_segment = false;
+
+    for &(ref run_range, style_index) in runs {
+        // Draw runs whose face can't be loaded with the primary face, rather than dropping them.
+        let style_index = match font_cache.face(styles[style_index].face.clone()) {
+            Ok(_) => style_index,
+            Err(_) => {
+                let style = RunStyle {
+                    face: primary_face.clone(),
+                    ..styles[style_index].clone()
+                };
+                push_style(styles, style)
+            }
+        };
+        let style = &styles[style_index];
+        let face = match font_cache.face(style.face.clone()) {
+            Ok(face) => face,
+            Err(_) => continue
+        };
+        shaped_text.clear();
+        shaper.shape_text(
+            &string[run_range.clone()],
+            face,
+            FaceSize::new(style.face_size, style.face_size),
+            dpi,
+            shaped_text
+        ).ok();
+
+        let mut segment_index = 0;
+        while let Some(segment) = shaped_text.get_segment(segment_index) {
+            let mut glyphs: Vec<_> = segment.shaped_glyphs.iter().map(|glyph| {
+                let mut glyph = glyph.clone();
+                let c = segment.text[glyph.word_str_index..].chars().next().unwrap();
+                glyph.str_index += run_range.start;
+                (glyph, c, style_index)
+            }).collect();
+            // Right-to-left runs get shaped in visual order. Layout works in logical order, and the
+            // glyphs get put back into visual order after line breaking.
+            glyphs.sort_by_key(|&(ref glyph, _, _)| glyph.str_index);
+
+            match (join_next_segment, segments.last_mut()) {
+                (true, Some(last_segment)) => {
+                    last_segment.glyphs.extend(glyphs);
+                    last_segment.hard_break = segment.hard_break;
+                },
+                _ => segments.push(StyledSegment {
+                    glyphs,
+                    hard_break: segment.hard_break
+                })
+            }
+            join_next_segment = false;
+            segment_index += 1;
+        }
+
+        // The end of a run is only a break opportunity if the run ends in whitespace. Otherwise,
+        // the run's last segment continues into the next run.
+        join_next_segment = string[..run_range.end].chars().next_back().map(|c| !c.is_whitespace()).unwrap_or(false);
+    }
+    shaped_text.clear();
+
+    segments
+}
+
+/// Split segments between every character, so that lines can break anywhere. Whitespace stays
+/// attached to the character before it, and glyphs in the same cluster are never split.
+fn split_segment_characters(segments: Vec<StyledSegment>) -> Vec<StyledSegment> {
+    let mut split_segments: Vec<StyledSegment> = Vec::new();
+
+    for segment in segments {
+        let mut last_str_index = None;
+        for glyph in segment.glyphs {
+            let (ref shaped_glyph, c, _) = glyph;
+            let starts_character = !c.is_whitespace() && last_str_index != Some(shaped_glyph.str_index);
+            last_str_index = Some(shaped_glyph.str_index);
+
+            match (starts_character, split_segments.last_mut()) {
+                (false, Some(last_segment)) => last_segment.glyphs.push(glyph),
+                _ => split_segments.push(StyledSegment {
+                    glyphs: vec![glyph],
+                    hard_break: false
+                })
+            }
+        }
+        if let Some(last_segment) = split_segments.last_mut() {
+            last_segment.hard_break = segment.hard_break;
+        }
+    }
+
+    split_segments
+}
+
+/// Replace the glyphs that don't fit within `width` with an ellipsis, removing glyphs from the
+/// side of the text given by `truncation`.
+///
+/// This treats all segments as being on a single line, so it should only be used on unwrapped text.
+fn truncate_segments(
+    segments: Vec<StyledSegment>,
+    ellipsis: &[(ShapedGlyph, char, usize)],
+    width: i32,
+    tab_advance: i32,
+    truncation: Truncation
+) -> Vec<StyledSegment>
+{
+    let hard_break = segments.last().map(|s| s.hard_break).unwrap_or(false);
+    let glyphs: Vec<_> = segments.into_iter().flat_map(|s| s.glyphs).collect();
+    let glyph_advance = |&(ref glyph, c, _): &(ShapedGlyph, char, usize)| match c {
+        // Tabs are approximated as a full tab stop, since their real advance depends on where
+        // they're drawn.
+        '\t' => tab_advance,
+        _ => glyph.advance.x
+    };
+
+    let text_advance: i32 = glyphs.iter().map(&glyph_advance).sum();
+    if text_advance <= width {
+        return vec![StyledSegment{ glyphs, hard_break }];
+    }
+
+    // Find the number of glyphs that can be taken from the given iterator within `max_advance`,
+    // without splitting a cluster.
+    let fit_glyphs = |glyphs: &mut Iterator<Item=&(ShapedGlyph, char, usize)>, max_advance: i32| {
+        let (mut count, mut advance, mut last_str_index) = (0, 0, None);
+        let mut cluster_start_count = 0;
+        for glyph in glyphs {
+            if last_str_index != Some(glyph.0.str_index) {
+                cluster_start_count = count;
+            }
+            advance += glyph_advance(glyph);
+            if max_advance < advance {
+                return cluster_start_count;
+            }
+            count += 1;
+            last_str_index = Some(glyph.0.str_index);
+        }
+        count
+    };
+
+    let ellipsis_advance: i32 = ellipsis.iter().map(&glyph_advance).sum();
+    let available_advance = cmp::max(width - ellipsis_advance, 0);
+    let (head_len, tail_len) = match truncation {
+        Truncation::None => (glyphs.len(), 0),
+        Truncation::End => (fit_glyphs(&mut glyphs.iter(), available_advance), 0),
+        Truncation::Start => (0, fit_glyphs(&mut glyphs.iter().rev(), available_advance)),
+        Truncation::Middle => {
+            let head_len = fit_glyphs(&mut glyphs.iter(), available_advance / 2);
+            let head_advance: i32 = glyphs[..head_len].iter().map(&glyph_advance).sum();
+            (head_len, fit_glyphs(&mut glyphs.iter().rev(), available_advance - head_advance))
+        }
+    };
+
+    let mut truncated_glyphs = Vec::with_capacity(head_len + ellipsis.len() + tail_len);
+    truncated_glyphs.extend(glyphs[..head_len].iter().cloned());
+    // Trailing whitespace before the ellipsis looks odd, so trim it.
+    while truncated_glyphs.last().map(|&(_, c, _)| c.is_whitespace()).unwrap_or(false) {
+        truncated_glyphs.pop();
+    }
+
+    // The ellipsis stands in for the first glyph it replaces.
+    let ellipsis_str_index = glyphs.get(head_len).map(|g| g.0.str_index).unwrap_or(0);
+    truncated_glyphs.extend(ellipsis.iter().cloned().map(|(mut glyph, c, style_index)| {
+        glyph.str_index = ellipsis_str_index;
+        (glyph, c, style_index)
+    }));
+    truncated_glyphs.extend(glyphs[glyphs.len() - tail_len..].iter().cloned());
+
+    vec![StyledSegment{ glyphs: truncated_glyphs, hard_break }]
+}
+
+/// Replace the composition text's range of the string with the composition text, if there is
+/// any. Composition text drawn over an invalid range gets ignored.
+fn layout_text<'a>(string: &'a str, preedit: &Option<(Range<usize>, String)>) -> Cow<'a, str> {
+    match *preedit {
+        Some((ref range, ref text)) if valid_preedit_range(string, range) => {
+            let mut layout_text = String::with_capacity(string.len() - range.len() + text.len());
+            layout_text.push_str(&string[..range.start]);
+            layout_text.push_str(text);
+            layout_text.push_str(&string[range.end..]);
+            Cow::Owned(layout_text)
+        },
+        _ => Cow::Borrowed(string)
+    }
+}
+
+fn valid_preedit_range(string: &str, range: &Range<usize>) -> bool {
+    range.start <= range.end && string.is_char_boundary(range.start) && string.is_char_boundary(range.end)
+}
+
+/// Shape `string` and lay it out within `rect`, filling `glyphs` with the laid-out glyphs. Returns
+/// the styles the glyphs reference and the minimum size needed to draw the string.
+fn layout_string(
+    string: &str,
+    spans: &[TextSpan],
+    rect: BoundBox<Point2<i32>>,
+    text_style: &ThemeText,
+    font_cache: &mut FontCache,
+    shaper: &mut Shaper,
+    shaped_text: &mut ShapedBuffer,
+    dpi: DPI,
+    mask: Option<char>,
+    glyphs: &mut Vec<RenderGlyph>
+) -> (Vec<RunStyle>, DimsBox<Point2<i32>>)
+{
+    let mut styles: Vec<RunStyle>;
+    let masked_string: String;
+    let bidi_info: Option<BidiInfo>;
+    let (shape_string, runs) = match mask {
+        Some(mask_char) => {
+            // Spans index into the real string, so they can't be applied to the masked one.
+            // Masked text is also never reordered, since that would reveal its direction.
+            masked_string = string.graphemes(true).map(|_| mask_char).collect();
+            bidi_info = None;
+            styles = vec![RunStyle::new(text_style, &SpanStyle::default(), font_cache)];
+            (&masked_string[..], vec![(0..masked_string.len(), 0)])
+        },
+        None => {
+            let info = BidiInfo::new(string, None);
+            let (mut run_styles, runs) = style_runs(spans, string, text_style, font_cache);
+            let runs = match text_style.fallback_faces.len() {
+                0 => runs,
+                _ => split_runs_by_coverage(string, runs, &mut run_styles, &text_style.fallback_faces, font_cache)
+            };
+            // Runs with different directions have to be shaped separately.
+            let runs = match info.has_rtl() {
+                true => split_runs_at_levels(runs, &info.levels),
+                false => runs
+            };
+            bidi_info = Some(info);
+            styles = run_styles;
+            (string, runs)
+        }
+    };
+    let segments = shape_segments(shape_string, &runs, &mut styles, &text_style.face, font_cache, shaper, shaped_text, dpi);
+    let ellipsis: Vec<_> = match text_style.truncation {
+        Truncation::None => Vec::new(),
+        _ => shape_segments(ELLIPSIS, &[(0..ELLIPSIS.len(), 0)], &mut styles, &text_style.face, font_cache, shaper, shaped_text, dpi)
+            .into_iter().flat_map(|segment| segment.glyphs).collect()
+    };
+    glyphs.clear();
+
+    // Compute the tab advance from the tab size and space advance. Used for tab stops.
+    let tab_advance = {
+        let face = font_cache.face(text_style.face.clone()).unwrap();
+        let space_glyph_index = face.char_index(' ');
+        let space_glyph_advance = (face.glyph_advance(
+            space_glyph_index,
+            FaceSize::new(text_style.face_size, text_style.face_size),
+            dpi,
+            LoadFlags::empty()
+        ).unwrap() + (1 << 15)) >> 16;
+
+        space_glyph_advance * text_style.tab_size as i32
+    };
+    let line_metrics = line_metrics(&styles, font_cache, dpi);
+
+    let mut glyph_iter = GlyphIter::new(rect, segments, &ellipsis, text_style, tab_advance, line_metrics);
+    glyphs.extend(&mut glyph_iter);
+    if let Some(ref bidi_info) = bidi_info {
+        reorder_bidi_lines(glyphs, bidi_info);
+    }
+
+    if let Some(mask_char) = mask {
+        // The glyphs index into the masked string, so map those indices back onto the
+        // graphemes in the real string.
+        let grapheme_indices: Vec<usize> = string.grapheme_indices(true).map(|(i, _)| i).collect();
+        for glyph in glyphs.iter_mut() {
+            glyph.str_index = grapheme_indices.get(glyph.str_index / mask_char.len_utf8()).cloned()
+                .unwrap_or(string.len());
+        }
+    }
+
+    let min_size = match text_style.line_wrap {
+        LineWrap::None => DimsBox::new2(
+            // withholding the +1 leads to clipping bugs so I'm just including it
+            glyph_iter.cursor.x - glyph_iter.line_start_x + 1,
+            glyph_iter.line_height + -glyph_iter.font_descender
+        ),
+        _ => DimsBox::new2(0, 0)
+    };
+
+    (styles, min_size)
+}
+
+/// Split runs wherever the run's face doesn't have a glyph for a grapheme, drawing the grapheme
+/// with the first fallback face that does. Graphemes that no face contains stay in the run's face.
+///
+/// New styles for the fallback faces get pushed onto `styles`.
+fn split_runs_by_coverage(
+    string: &str,
+    runs: Vec<(Range<usize>, usize)>,
+    styles: &mut Vec<RunStyle>,
+    fallback_faces: &[ThemeFace],
+    font_cache: &mut FontCache
+) -> Vec<(Range<usize>, usize)>
+{
+    let mut split_runs: Vec<(Range<usize>, usize)> = Vec::with_capacity(runs.len());
+    for (run_range, style_index) in runs {
+        let mut current_style = style_index;
+        for (i, grapheme) in string[run_range.clone()].grapheme_indices(true) {
+            let grapheme_start = run_range.start + i;
+            let c = grapheme.chars().next().unwrap();
+
+            // Whitespace doesn't need a specific face, so keep it in the current face rather than
+            // splitting the run.
+            if !c.is_whitespace() {
+                let run_face = styles[style_index].face.clone();
+                let face = font_cache.face_for_char(Some(&run_face).into_iter().chain(fallback_faces), c).cloned();
+                current_style = match face {
+                    Some(ref face) if *face != run_face => {
+                        let style = RunStyle {
+                            face: face.clone(),
+                            ..styles[style_index].clone()
+                        };
+                        push_style(styles, style)
+                    },
+                    _ => style_index
+                };
+            }
+
+            match split_runs.last_mut() {
+                Some(&mut (ref mut split_range, split_style)) if
+                    split_style == current_style &&
+                    split_range.end == grapheme_start
+                    => split_range.end += grapheme.len(),
+                _ => split_runs.push((grapheme_start..grapheme_start + grapheme.len(), current_style))
+            }
+        }
+    }
+    split_runs
+}
+
+/// Get the index of `style` in `styles`, adding it to the end of `styles` if it isn't there yet.
+fn push_style(styles: &mut Vec<RunStyle>, style: RunStyle) -> usize {
+    match styles.iter().position(|s| *s == style) {
+        Some(index) => index,
+        None => {
+            styles.push(style);
+            styles.len() - 1
+        }
+    }
+}
+
+/// Split style runs wherever the bidi embedding level changes.
+fn split_runs_at_levels(runs: Vec<(Range<usize>, usize)>, levels: &[Level]) -> Vec<(Range<usize>, usize)> {
+    let mut split_runs = Vec::with_capacity(runs.len());
+    for (run_range, style_index) in runs {
+        let mut run_start = run_range.start;
+        for i in run_range.start + 1..run_range.end {
+            if levels[i] != levels[i - 1] {
+                split_runs.push((run_start..i, style_index));
+                run_start = i;
+            }
+        }
+        split_runs.push((run_start..run_range.end, style_index));
+    }
+    split_runs
+}
+
+/// Reorder the glyphs on each line from logical to visual order, following rules L1 and L2 of
+/// [UAX #9](https://unicode.org/reports/tr9/), and move the glyphs to their visual positions.
+///
+/// Lines are expected to be in order, with each line's glyphs contiguous and in logical order.
+fn reorder_bidi_lines(glyphs: &mut [RenderGlyph], bidi_info: &BidiInfo) {
+    if !bidi_info.has_rtl() {
+        return;
+    }
+
+    let paragraph_level = |str_index: usize| bidi_info.paragraphs.iter()
+        .find(|p| p.range.contains(str_index))
+        .map(|p| p.level)
+        .unwrap_or(Level::ltr());
+
+    let mut line_start = 0;
+    while line_start < glyphs.len() {
+        let line_y = glyphs[line_start].pos.y;
+        let line_len = glyphs[line_start..].iter().take_while(|g| g.pos.y == line_y).count();
+        let line = &mut glyphs[line_start..line_start + line_len];
+        line_start += line_len;
+
+        let mut levels: Vec<Level> = line.iter().map(|g| bidi_info.levels.get(g.str_index).cloned().unwrap_or(Level::ltr())).collect();
+        // L1: Whitespace at the end of a line gets reset to the paragraph level. Whitespace and tabs
+        // are the only glyphs without a glyph index.
+        for (glyph, level) in line.iter().zip(levels.iter_mut()).rev() {
+            if glyph.glyph_index.is_some() {
+                break;
+            }
+            *level = paragraph_level(glyph.str_index);
+        }
+        for (glyph, level) in line.iter_mut().zip(&levels) {
+            glyph.rtl = level.is_rtl();
+        }
+
+        // L2: From the highest level down to the lowest odd level, reverse every sequence of glyphs
+        // at that level or higher.
+        let max_level = levels.iter().map(|l| l.number()).max().unwrap_or(0);
+        let min_odd_level = levels.iter().map(|l| l.number()).min().unwrap_or(0) | 1;
+        let mut order: Vec<usize> = (0..line.len()).collect();
+        for reverse_level in (min_odd_level..max_level + 1).rev() {
+            let mut i = 0;
+            while i < order.len() {
+                let run_len = order[i..].iter().take_while(|&&g| levels[g].number() >= reverse_level).count();
+                order[i..i + run_len].reverse();
+                i += cmp::max(run_len, 1);
+            }
+        }
+
+        let line_glyphs: Vec<RenderGlyph> = line.iter().cloned().collect();
+        let mut x = line_glyphs.iter().map(|g| g.highlight_rect.min.x).min().unwrap_or(0);
+        for (glyph, &logical_index) in line.iter_mut().zip(&order) {
+            *glyph = line_glyphs[logical_index];
+            let dx = x - glyph.highlight_rect.min.x;
+            glyph.pos.x += dx;
+            glyph.highlight_rect = glyph.highlight_rect + Vector2::new(dx, 0);
+            x += glyph.highlight_rect.width();
+        }
+    }
+}
+
+fn line_metrics(styles: &[RunStyle], font_cache: &mut FontCache, dpi: DPI) -> LineMetrics {
+    styles.iter()
+        .filter_map(|style| {
+            let face_size = FaceSize::new(style.face_size, style.face_size);
+            let font_metrics = font_cache.face(style.face.clone()).ok()?.metrics_sized(face_size, dpi).ok()?;
+            Some(LineMetrics {
+                line_height: (font_metrics.height / 64) as i32,
+                ascender: (font_metrics.ascender / 64) as i32,
+                descender: (font_metrics.descender / 64) as i32
+            })
+        })
+        .fold(LineMetrics::default(), |max, metrics| LineMetrics {
+            line_height: cmp::max(max.line_height, metrics.line_height),
+            ascender: cmp::max(max.ascender, metrics.ascender),
+            descender: cmp::min(max.descender, metrics.descender)
+        })
 }
 
 impl RenderGlyph {
@@ -1180,4 +2367,311 @@ impl RenderGlyph {
         self.highlight_rect = self.highlight_rect + offset;
         self
     }
+
+    /// The horizontal position of a cursor placed logically before this glyph.
+    fn cursor_x(&self) -> i32 {
+        match self.rtl {
+            false => self.highlight_rect.min.x,
+            true => self.highlight_rect.max.x
+        }
+    }
+
+    /// Whether the point `x` lies logically after the center of this glyph.
+    fn after_center(&self, x: i32) -> bool {
+        let center_x = self.highlight_rect.center().x;
+        match self.rtl {
+            false => center_x <= x,
+            true => x < center_x
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use theme::Theme;
+    use core::render::Theme as CoreTheme;
+    use dct::layout::{Align2, Margins};
+
+    const ALPHABET: &str = "abcdefghijklmnopqrstuvwxyz";
+
+    fn text_style(line_wrap: LineWrap, truncation: Truncation) -> ThemeText {
+        let mut text_style = Theme::default().widget_theme("Label").text.unwrap();
+        text_style.justify = Align2::new(Align::Start, Align::Start);
+        text_style.margins = Margins::new(0, 0, 0, 0);
+        text_style.line_wrap = line_wrap;
+        text_style.truncation = truncation;
+        text_style
+    }
+
+    /// Lay out `string` in a box `width` pixels wide, returning the glyphs that get drawn.
+    fn layout(string: &str, width: i32, text_style: &ThemeText) -> Vec<RenderGlyph> {
+        let mut render_string = RenderString::new(string.to_string());
+        let (glyphs, _) = render_string.reshape_glyphs(
+            BoundBox::new2(0, 0, width, 1024), text_style,
+            &mut FontCache::new(), &mut Shaper::new(), &mut ShapedBuffer::new(), DPI::new(72, 72),
+            None, None
+        );
+        glyphs.iter().cloned().filter(|g| g.glyph_index.is_some()).collect()
+    }
+
+    /// Lay out `edit_string` in a box `width` pixels wide, as if it had been drawn.
+    fn lay_out_edit_string(edit_string: &mut EditString, width: i32, text_style: &ThemeText) {
+        edit_string.render_string.reshape_glyphs(
+            BoundBox::new2(0, 0, width, 1024), text_style,
+            &mut FontCache::new(), &mut Shaper::new(), &mut ShapedBuffer::new(), DPI::new(72, 72),
+            None, None
+        );
+    }
+
+    fn ellipsis_index(text_style: &ThemeText) -> Option<u32> {
+        let mut font_cache = FontCache::new();
+        let face = font_cache.face(text_style.face.clone()).unwrap();
+        Some(face.char_index('\u{2026}'))
+    }
+
+    #[test]
+    fn wrap_character() {
+        let glyphs = layout(ALPHABET, 60, &text_style(LineWrap::Character, Truncation::None));
+
+        // Every character gets drawn, in order, without overflowing the box.
+        assert_eq!((0..ALPHABET.len()).collect::<Vec<_>>(), glyphs.iter().map(|g| g.str_index).collect::<Vec<_>>());
+        assert!(glyphs.iter().all(|g| g.highlight_rect.max.x <= 60));
+
+        let line_count = glyphs.iter().map(|g| g.pos.y).dedup().count();
+        assert!(line_count > 1);
+    }
+
+    #[test]
+    fn truncate_end() {
+        let style = text_style(LineWrap::None, Truncation::End);
+        let glyphs = layout(ALPHABET, 100, &style);
+
+        assert_eq!(0, glyphs.first().unwrap().str_index);
+        assert_eq!(ellipsis_index(&style), glyphs.last().unwrap().glyph_index);
+        assert!(glyphs.iter().all(|g| g.str_index < ALPHABET.len() - 1));
+    }
+
+    #[test]
+    fn truncate_start() {
+        let style = text_style(LineWrap::None, Truncation::Start);
+        let glyphs = layout(ALPHABET, 100, &style);
+
+        assert_eq!(ellipsis_index(&style), glyphs.first().unwrap().glyph_index);
+        assert_eq!(ALPHABET.len() - 1, glyphs.last().unwrap().str_index);
+        assert!(glyphs[1..].iter().all(|g| g.str_index > 0));
+    }
+
+    #[test]
+    fn truncate_middle() {
+        let style = text_style(LineWrap::None, Truncation::Middle);
+        let glyphs = layout(ALPHABET, 100, &style);
+
+        assert_eq!(0, glyphs.first().unwrap().str_index);
+        assert_eq!(ALPHABET.len() - 1, glyphs.last().unwrap().str_index);
+        let ellipsis_pos = glyphs.iter().position(|g| g.glyph_index == ellipsis_index(&style)).unwrap();
+        assert!(0 < ellipsis_pos && ellipsis_pos < glyphs.len() - 1);
+        assert!(glyphs.len() < ALPHABET.len());
+    }
+
+    #[test]
+    fn backspace_rtl() {
+        let mut edit_string = EditString::new(RenderString::new("שלום".to_string()));
+        *edit_string.cursor_pos_mut() = edit_string.render_string.string().len();
+
+        edit_string.delete_chars(-1, false);
+        assert_eq!("שלו", edit_string.render_string.string());
+        assert_eq!("שלו".len(), edit_string.cursor_pos());
+
+        edit_string.delete_chars(1, false);
+        assert_eq!("שלו", edit_string.render_string.string());
+    }
+
+    #[test]
+    fn preedit_not_in_string() {
+        let mut edit_string = EditString::new(RenderString::new("ab".to_string()));
+        *edit_string.cursor_pos_mut() = 1;
+        edit_string.set_max_len(Some(4));
+
+        edit_string.set_preedit("xyz", Some((1, 2)));
+        assert_eq!("ab", edit_string.render_string.string());
+        assert_eq!(Some("xy"), edit_string.preedit());
+        assert_eq!(1..3, edit_string.preedit_range());
+        assert_eq!("axyb", edit_string.render_string.layout_text());
+        assert_eq!(1, edit_string.render_string.string_index(2));
+        assert_eq!(2, edit_string.render_string.string_index(4));
+
+        edit_string.clear_preedit();
+        assert_eq!(None, edit_string.preedit());
+        assert_eq!("ab", edit_string.render_string.layout_text());
+    }
+
+    #[test]
+    fn preedit_hides_selection() {
+        let mut edit_string = EditString::new(RenderString::new("abc".to_string()));
+        edit_string.select_all();
+
+        edit_string.set_preedit("xy", None);
+        assert_eq!("abc", edit_string.render_string.string());
+        assert_eq!(0..2, edit_string.preedit_range());
+        assert_eq!("xy", edit_string.render_string.layout_text());
+        assert_eq!(0, edit_string.render_string.string_index(1));
+        assert_eq!(3, edit_string.render_string.string_index(2));
+    }
+
+    #[test]
+    fn move_cursor_visual_rtl() {
+        let style = text_style(LineWrap::None, Truncation::None);
+        let mut edit_string = EditString::new(RenderString::new("שלום".to_string()));
+        lay_out_edit_string(&mut edit_string, 1024, &style);
+
+        edit_string.move_cursor_visual(-1, false, false);
+        assert_eq!("ש".len(), edit_string.cursor_pos());
+        edit_string.move_cursor_visual(1, false, false);
+        assert_eq!(0, edit_string.cursor_pos());
+        edit_string.move_cursor_visual(1, false, false);
+        assert_eq!(0, edit_string.cursor_pos());
+    }
+
+    #[test]
+    fn cursor_at_end_of_rtl() {
+        let style = text_style(LineWrap::None, Truncation::None);
+        let mut edit_string = EditString::new(RenderString::new("שלום".to_string()));
+        lay_out_edit_string(&mut edit_string, 1024, &style);
+        *edit_string.cursor_pos_mut() = edit_string.render_string.string().len();
+
+        // The end of right-to-left text is drawn on its left.
+        let text_left = layout("שלום", 1024, &style).iter().map(|g| g.highlight_rect.min.x).min().unwrap();
+        assert_eq!(text_left, edit_string.cursor_rect().unwrap().min.x);
+    }
+
+    #[test]
+    fn move_cursor_visual_mixed() {
+        let style = text_style(LineWrap::None, Truncation::None);
+        let string = "abc שלום def";
+        let mut edit_string = EditString::new(RenderString::new(string.to_string()));
+        lay_out_edit_string(&mut edit_string, 1024, &style);
+        let mut visited = vec![edit_string.cursor_pos()];
+        let mut cursor_x = edit_string.cursor_rect().unwrap().min.x;
+
+        // Moving right from the left edge visits every grapheme boundary, without ever moving left.
+        for _ in 0..string.chars().count() {
+            edit_string.move_cursor_visual(1, false, false);
+            let new_cursor_x = edit_string.cursor_rect().unwrap().min.x;
+            assert!(cursor_x <= new_cursor_x);
+            cursor_x = new_cursor_x;
+            visited.push(edit_string.cursor_pos());
+        }
+        visited.sort();
+        visited.dedup();
+        assert_eq!(string.char_indices().map(|(i, _)| i).chain(Some(string.len())).collect::<Vec<_>>(), visited);
+
+        // Moving left retraces the same stops.
+        for _ in 0..string.chars().count() {
+            edit_string.move_cursor_visual(-1, false, false);
+        }
+        assert_eq!(0, edit_string.cursor_pos());
+    }
+
+    #[test]
+    fn line_boundary_wrapped() {
+        let style = text_style(LineWrap::Character, Truncation::None);
+        let glyphs = layout(ALPHABET, 60, &style);
+        let line_starts: Vec<usize> = glyphs.iter().enumerate()
+            .filter(|&(i, g)| i == 0 || g.pos.y != glyphs[i - 1].pos.y)
+            .map(|(_, g)| g.str_index)
+            .collect();
+        let next_line_start = line_starts.get(2).cloned().unwrap_or(ALPHABET.len());
+
+        let mut edit_string = EditString::new(RenderString::new(ALPHABET.to_string()));
+        lay_out_edit_string(&mut edit_string, 60, &style);
+        assert_eq!(line_starts[1]..next_line_start, edit_string.line_range(line_starts[1] + 1));
+
+        *edit_string.cursor_pos_mut() = line_starts[1] + 1;
+        edit_string.move_cursor_line_boundary(-1, false);
+        assert_eq!(line_starts[1], edit_string.cursor_pos());
+        edit_string.move_cursor_line_boundary(1, false);
+        match line_starts.len() > 2 {
+            true => assert_eq!(next_line_start - 1, edit_string.cursor_pos()),
+            false => assert_eq!(ALPHABET.len(), edit_string.cursor_pos())
+        }
+    }
+
+    #[test]
+    fn line_boundary_hard_break() {
+        let style = text_style(LineWrap::Normal, Truncation::None);
+        let mut edit_string = EditString::new(RenderString::new("ab\ncd".to_string()));
+        lay_out_edit_string(&mut edit_string, 1024, &style);
+        assert_eq!(0..2, edit_string.line_range(1));
+        assert_eq!(3..5, edit_string.line_range(4));
+
+        *edit_string.cursor_pos_mut() = 4;
+        edit_string.move_cursor_line_boundary(-1, false);
+        assert_eq!(3, edit_string.cursor_pos());
+        *edit_string.cursor_pos_mut() = 0;
+        edit_string.move_cursor_line_boundary(1, false);
+        assert_eq!(2, edit_string.cursor_pos());
+    }
+
+    #[test]
+    fn style_runs_in_chars() {
+        let spans = [
+            TextSpan{ range: 0..4, style: SpanStyle{ underline: Some(true), ..SpanStyle::default() } },
+            // Starts in the middle of the 'é', so gets moved back to the start of it.
+            TextSpan{ range: 2..4, style: SpanStyle{ underline: Some(false), ..SpanStyle::default() } }
+        ];
+        let (styles, runs) = style_runs(&spans, "aéb", &text_style(LineWrap::None, Truncation::None), &mut FontCache::new());
+
+        // The later span turns the earlier span's underline back off.
+        assert_eq!(vec![(0..1, 1), (1..4, 0)], runs);
+        assert!(styles[1].underline);
+        assert!(!styles[0].underline);
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut edit_string = EditString::new(RenderString::new(String::new()));
+        // Consecutive typing gets undone as one edit.
+        for c in "ab".chars() {
+            edit_string.insert_char(c);
+        }
+        *edit_string.cursor_pos_mut() = 0;
+        edit_string.insert_char('c');
+        assert_eq!("cab", edit_string.render_string.string());
+
+        assert!(edit_string.undo());
+        assert_eq!("ab", edit_string.render_string.string());
+        assert_eq!(0, edit_string.cursor_pos());
+        assert!(edit_string.undo());
+        assert_eq!("", edit_string.render_string.string());
+        assert!(!edit_string.undo());
+
+        assert!(edit_string.redo());
+        assert_eq!("ab", edit_string.render_string.string());
+        assert!(edit_string.redo());
+        assert_eq!("cab", edit_string.render_string.string());
+        assert!(!edit_string.redo());
+
+        // Editing after undoing throws away the undone edits.
+        edit_string.undo();
+        edit_string.delete_chars(1, false);
+        assert!(!edit_string.redo());
+        assert_eq!("b", edit_string.render_string.string());
+    }
+
+    #[test]
+    fn undo_limit() {
+        let mut edit_string = EditString::new(RenderString::new(String::new()));
+        for _ in 0..MAX_UNDO_LEN + 1 {
+            edit_string.insert_str("a");
+        }
+
+        let mut undo_count = 0;
+        while edit_string.undo() {
+            undo_count += 1;
+        }
+        // The oldest edit gets dropped once the limit is hit.
+        assert_eq!(MAX_UNDO_LEN, undo_count);
+        assert_eq!("a", edit_string.render_string.string());
+    }
 }
@@ -5,8 +5,10 @@ use dct::buttons::{MouseButton, Key, ModifierKeys};
 use core::{Root, LoopFlow, WindowEvent, EventLoopOps, PopupDelta};
 use core::tree::{Widget, WidgetIdent};
 use core::event::WidgetEvent;
+use core::clipboard::Clipboard;
 use core::popup::PopupID;
 use theme::Theme;
+use system_clipboard::SystemClipboard;
 use gullery::ContextState;
 
 use std::thread::{self, JoinHandle};
@@ -15,7 +17,7 @@ use std::cell::{Cell, RefCell};
 use std::time::Duration;
 use std::collections::HashMap;
 use std::rc::Rc;
-use cgmath::Point2;
+use cgmath::{Point2, Vector2};
 use cgmath_geometry::{DimsBox, GeoBox};
 
 use parking_lot::Mutex;
@@ -69,8 +71,11 @@ impl<A, N: Widget<A, GLFrame>> GlutinWindow<A, N> {
             }
         });
 
+        let mut root = Root::new(root, theme, renderer.dims());
+        root.set_clipboard(Box::new(SystemClipboard::new()));
+
         Ok(GlutinWindow {
-            root: Root::new(root, theme, renderer.dims()),
+            root,
             primary_renderer: RefCell::new(renderer),
             active_renderer: Cell::new(None),
             window_popup_map: HashMap::new(),
@@ -93,6 +98,11 @@ impl<A, N: Widget<A, GLFrame>> GlutinWindow<A, N> {
         &mut self.root.root_widget
     }
 
+    /// Replace the clipboard used by the window's widgets. Defaults to the `SystemClipboard`.
+    pub fn set_clipboard(&mut self, clipboard: impl Clipboard + 'static) {
+        self.root.set_clipboard(Box::new(clipboard));
+    }
+
     pub fn run_forever<F, FF, R>(&mut self, on_action: F, on_fallthrough: FF) -> Option<R>
         where F: FnMut(A, &mut N, &mut Theme) -> LoopFlow<R>,
               FF: FnMut(WidgetEvent, &[WidgetIdent]) -> Option<A>
@@ -118,6 +128,8 @@ impl<A, N: Widget<A, GLFrame>> GlutinWindow<A, N> {
                 modifiers.set(ModifierKeys::LOGO, g_modifiers.logo);
                 modifiers
             };
+            // Fractional lines scrolled that haven't been delivered to widgets yet.
+            let mut scroll_lines: Vector2<f32> = Vector2::new(0.0, 0.0);
 
             loop {
                 let mut popup_deltas = Vec::new();
@@ -148,7 +160,26 @@ impl<A, N: Widget<A, GLFrame>> GlutinWindow<A, N> {
                                         ElementState::Released => WindowEvent::MouseUp(button)
                                     }
                                 }
+                                GWindowEvent::MouseWheel{delta, modifiers, ..} => {
+                                    event_loop_ops.set_modifiers(map_modifiers(modifiers));
+                                    match delta {
+                                        MouseScrollDelta::LineDelta(x, y) => {
+                                            // Touchpads and smooth-scrolling wheels report fractional lines, so
+                                            // carry the fractions over until they add up to whole lines.
+                                            scroll_lines += Vector2::new(x, y);
+                                            let lines = Vector2::new(scroll_lines.x.trunc(), scroll_lines.y.trunc());
+                                            scroll_lines -= lines;
+                                            if lines == Vector2::new(0.0, 0.0) {
+                                                return ControlFlow::Continue;
+                                            }
+                                            WindowEvent::MouseScrollLines(Vector2::new(lines.x as i32, lines.y as i32))
+                                        },
+                                        MouseScrollDelta::PixelDelta(x, y) => WindowEvent::MouseScrollPx(Vector2::new(x as i32, y as i32))
+                                    }
+                                },
                                 GWindowEvent::Resized(width, height) => WindowEvent::WindowResize(DimsBox::new2(width, height)),
+                                // Winit doesn't report IME composition, and delivers committed IME text
+                                // as received characters, so `ImePreedit` and `ImeCommit` are never sent.
                                 GWindowEvent::ReceivedCharacter(c) => WindowEvent::Char(c),
                                 GWindowEvent::KeyboardInput{ input, .. } => {
                                     if let Some(key) = input.virtual_keycode.and_then(map_key) {
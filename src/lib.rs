@@ -38,6 +38,7 @@ extern crate arrayvec;
 extern crate glyphydog;
 extern crate itertools;
 extern crate unicode_segmentation;
+extern crate unicode_bidi;
 extern crate clipboard;
 extern crate png;
 extern crate parking_lot;
@@ -45,6 +46,7 @@ extern crate parking_lot;
 pub mod container;
 pub mod gl_render;
 mod glutin_window;
+mod system_clipboard;
 pub mod layout;
 pub mod theme;
 pub mod widgets;
@@ -55,8 +57,10 @@ pub use core::LoopFlow;
 
 /// `WidgetEvent` type and associated helpers.
 pub mod event {
-    pub use core::event::{EventOps, InputState, MouseDown, FocusChange, WidgetEvent};
+    pub use core::event::{EventOps, EventContext, InputState, MouseDown, FocusChange, WidgetEvent};
     pub use dct::buttons::{ModifierKeys, Key, MouseButton};
+    pub use core::clipboard::{Clipboard, ClipboardKind, MemoryClipboard};
+    pub use system_clipboard::SystemClipboard;
 }
 
 /// Types used to assemble widget geometry.
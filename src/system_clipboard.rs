@@ -0,0 +1,62 @@
+use core::clipboard::{Clipboard, ClipboardKind};
+
+use clipboard::{ClipboardContext, ClipboardProvider};
+#[cfg(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))]
+use clipboard::x11_clipboard::{X11ClipboardContext, Primary};
+
+/// The operating system's clipboard. This is the clipboard used by `Window` by default.
+///
+/// On X11, this also provides access to the primary selection.
+pub struct SystemClipboard {
+    clipboard: Option<ClipboardContext>,
+    #[cfg(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))]
+    primary: Option<X11ClipboardContext<Primary>>
+}
+
+impl SystemClipboard {
+    pub fn new() -> SystemClipboard {
+        SystemClipboard {
+            clipboard: ClipboardContext::new().ok(),
+            #[cfg(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))]
+            primary: X11ClipboardContext::new().ok()
+        }
+    }
+
+    #[cfg(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))]
+    fn get_primary(&mut self) -> Option<String> {
+        self.primary.as_mut().and_then(|p| p.get_contents().ok())
+    }
+
+    #[cfg(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))]
+    fn set_primary(&mut self, contents: String) {
+        if let Some(ref mut primary) = self.primary {
+            primary.set_contents(contents).ok();
+        }
+    }
+
+    #[cfg(not(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten")))))]
+    fn get_primary(&mut self) -> Option<String> {
+        None
+    }
+
+    #[cfg(not(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten")))))]
+    fn set_primary(&mut self, _: String) {}
+}
+
+impl Clipboard for SystemClipboard {
+    fn get_contents(&mut self, kind: ClipboardKind) -> Option<String> {
+        match kind {
+            ClipboardKind::Clipboard => self.clipboard.as_mut().and_then(|c| c.get_contents().ok()),
+            ClipboardKind::Primary => self.get_primary()
+        }
+    }
+
+    fn set_contents(&mut self, kind: ClipboardKind, contents: String) {
+        match kind {
+            ClipboardKind::Clipboard => if let Some(ref mut clipboard) = self.clipboard {
+                clipboard.set_contents(contents).ok();
+            },
+            ClipboardKind::Primary => self.set_primary(contents)
+        }
+    }
+}
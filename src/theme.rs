@@ -5,15 +5,17 @@ use gullery::colors::Rgba;
 use gullery::glsl::Nu8;
 
 use cgmath::Point2;
-use cgmath_geometry::DimsBox;
+use cgmath_geometry::{DimsBox, GeoBox};
 use dct::layout::{Align, Align2, Margins};
 
 use std::io;
 use std::rc::Rc;
 use std::path::Path;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use core::render::Theme as CoreTheme;
+use gl_render::TextMetrics;
 pub use dct::cursor::CursorIcon;
 
 pub mod color {
@@ -43,13 +45,63 @@ pub enum RescaleRules {
     Align(Align2)
 }
 
+/// A rectangle drawn with vector graphics, rather than from an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ThemeShape {
+    /// How the inside of the shape is filled.
+    pub fill: Fill,
+    /// The width of the shape's border, in pixels. The border is drawn inside of the shape's rect.
+    pub border_width: u16,
+    /// The color of the shape's border.
+    pub border_color: Rgba<Nu8>,
+    /// The radius of the shape's corners, in pixels.
+    pub corner_radius: u16
+}
+
+/// How to fill the inside of a `ThemeShape`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fill {
+    /// Don't fill the shape, only drawing its border.
+    None,
+    /// Fill the shape with a single color.
+    Color(Rgba<Nu8>),
+    /// Fill the shape with a gradient running from the top of the shape to the bottom.
+    VerticalGradient {
+        top: Rgba<Nu8>,
+        bottom: Rgba<Nu8>
+    },
+    /// Fill the shape with a gradient running from the left of the shape to the right.
+    HorizontalGradient {
+        left: Rgba<Nu8>,
+        right: Rgba<Nu8>
+    }
+}
+
 /// The algorithm used to determine where line breaks occur in text.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum LineWrap {
     /// Disallow all line breaks, including explicit ones (such as from `'\n'`).
     None,
     /// Allow line breaks at break points, as defined by [UAX #14](https://unicode.org/reports/tr14/).
-    Normal
+    Normal,
+    /// Allow line breaks between any two characters. Useful for long strings without break points,
+    /// such as paths and hashes.
+    Character
+}
+
+/// Where text gets replaced with an ellipsis (`…`) when it's too wide to fit in its draw box.
+///
+/// Truncation is only performed on text that isn't wrapped, i.e. text drawn with `LineWrap::None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Truncation {
+    /// Don't truncate the text, letting it overflow the draw box.
+    None,
+    /// Remove text from the start of the string.
+    Start,
+    /// Remove text from the middle of the string, keeping its start and end visible.
+    Middle,
+    /// Remove text from the end of the string.
+    End
 }
 
 /// Collection of information used to determine how to render text in a widget.
@@ -57,6 +109,14 @@ pub enum LineWrap {
 pub struct ThemeText {
     /// A handle to the font face used to draw the text.
     pub face: ThemeFace,
+    /// The face used to draw bold text. Falls back to `face` if `None`.
+    pub bold_face: Option<ThemeFace>,
+    /// The face used to draw italic text. Falls back to `face` if `None`.
+    pub italic_face: Option<ThemeFace>,
+    /// The face used to draw bold italic text. Falls back to `bold_face`, then `italic_face`, if `None`.
+    pub bold_italic_face: Option<ThemeFace>,
+    /// Faces used, in order, to draw characters that the text's face doesn't contain.
+    pub fallback_faces: Vec<ThemeFace>,
     /// The color to draw text.
     pub color: Rgba<Nu8>,
     /// The color of the highlight when highlighting text.
@@ -74,13 +134,37 @@ pub struct ThemeText {
     /// The number of pixels on the sides of a draw box in which text shouldn't be drawn.
     pub margins: Margins<u16>,
     /// The line wrapping algorithm.
-    pub line_wrap: LineWrap
+    pub line_wrap: LineWrap,
+    /// How to truncate text that's too wide to fit in its draw box.
+    pub truncation: Truncation
+}
+
+/// Overrides to a `ThemeText`'s style, applied to a span of text.
+///
+/// `None` fields use the value from the `ThemeText` the text is drawn with.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct SpanStyle {
+    /// Whether to draw the text with the theme's bold face.
+    pub bold: Option<bool>,
+    /// Whether to draw the text with the theme's italic face.
+    pub italic: Option<bool>,
+    /// The color to draw the text.
+    pub color: Option<Rgba<Nu8>>,
+    /// The size of the text, in 64ths of a point.
+    pub face_size: Option<u32>,
+    /// Whether to draw a line under the text.
+    pub underline: Option<bool>,
+    /// Whether to draw a line through the text.
+    pub strikethrough: Option<bool>
 }
 
-/// The text style and image used to draw a widget with a given style.
+/// The text style, shape, and image used to draw a widget with a given style.
+///
+/// If both a shape and an image are provided, the image is drawn on top of the shape.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ThemeWidget {
     pub text: Option<ThemeText>,
+    pub shape: Option<ThemeShape>,
     pub image: Option<Rc<Image>>,
 }
 
@@ -92,9 +176,14 @@ pub struct ThemeFace {
 }
 
 pub struct Theme {
-    map: HashMap<String, ThemeWidget>
+    map: HashMap<String, ThemeWidget>,
+    text_metrics: TextMetrics,
+    generation: usize
 }
 
+/// The generation given to the next theme that gets created or modified.
+static NEXT_GENERATION: AtomicUsize = AtomicUsize::new(0);
+
 
 impl ThemeFace {
     /// Create a new face, referencing the font file at the provided path.
@@ -122,13 +211,29 @@ impl ThemeFace {
 impl Theme {
     pub fn empty() -> Theme {
         Theme {
-            map: HashMap::new()
+            map: HashMap::new(),
+            text_metrics: TextMetrics::new(),
+            generation: NEXT_GENERATION.fetch_add(1, Ordering::Relaxed)
         }
     }
 
     pub fn insert_widget(&mut self, key: String, theme: ThemeWidget) -> Option<ThemeWidget> {
+        self.generation = NEXT_GENERATION.fetch_add(1, Ordering::Relaxed);
         self.map.insert(key, theme)
     }
+
+    /// Get a number that changes whenever the theme gets modified. No two themes share the same
+    /// generation.
+    #[inline]
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// Get the theme's text measurer, which can measure text before it's drawn.
+    #[inline]
+    pub fn text_metrics(&self) -> &TextMetrics {
+        &self.text_metrics
+    }
 }
 
 impl CoreTheme for Theme {
@@ -139,6 +244,7 @@ impl CoreTheme for Theme {
         self.map.get(path).cloned().unwrap_or(
             ThemeWidget {
                 text: None,
+                shape: None,
                 image: None
             }
         )
@@ -164,6 +270,10 @@ impl Default for Theme {
                         text: Some(ThemeText {
                             // TODO: DON'T LOAD FROM SRC
                             face: ThemeFace::new("./src/default_theme_resources/DejaVuSans.ttf", 0).unwrap(),
+                            bold_face: None,
+                            italic_face: None,
+                            bold_italic_face: None,
+                            fallback_faces: Vec::new(),
                             color: Rgba::new(Nu8(0), Nu8(0), Nu8(0), Nu8(255)),
                             highlight_bg_color: Rgba::new(Nu8(0), Nu8(120), Nu8(215), Nu8(255)),
                             highlight_text_color: Rgba::new(Nu8(255), Nu8(255), Nu8(255), Nu8(255)),
@@ -171,8 +281,10 @@ impl Default for Theme {
                             tab_size: 8,
                             justify: $align,
                             margins: Margins::new($border, $border, $border, $border),
-                            line_wrap: LineWrap::None
+                            line_wrap: LineWrap::None,
+                            truncation: Truncation::None
                         }),
+                        shape: None,
                         image: Some(Rc::new(Image {
                             pixels: unsafe {
                                 Vec::from_raw_parts(
@@ -196,11 +308,38 @@ impl Default for Theme {
         upload_image!("Button::Hover", "./default_theme_resources/button.hover.png", 32, 4, Align2::new(Align::Center, Align::Center));
         upload_image!("Button::Clicked", "./default_theme_resources/button.clicked.png", 32, 4, Align2::new(Align::Center, Align::Center));
         upload_image!("EditBox", "./default_theme_resources/editbox.png", 8, 3, Align2::new(Align::Start, Align::Center));
+        theme.insert_widget(
+            "EditBox::Placeholder".to_string(),
+            ThemeWidget {
+                text: Some(ThemeText {
+                    face: ThemeFace::new("./src/default_theme_resources/DejaVuSans.ttf", 0).unwrap(),
+                    bold_face: None,
+                    italic_face: None,
+                    bold_italic_face: None,
+                    fallback_faces: Vec::new(),
+                    color: Rgba::new(Nu8(128), Nu8(128), Nu8(128), Nu8(255)),
+                    highlight_bg_color: Rgba::new(Nu8(0), Nu8(120), Nu8(215), Nu8(255)),
+                    highlight_text_color: Rgba::new(Nu8(255), Nu8(255), Nu8(255), Nu8(255)),
+                    face_size: 16 * 64,
+                    tab_size: 8,
+                    justify: Align2::new(Align::Start, Align::Center),
+                    margins: Margins::new(3, 3, 3, 3),
+                    line_wrap: LineWrap::None,
+                    truncation: Truncation::None
+                }),
+                shape: None,
+                image: None
+            }
+        );
         theme.insert_widget(
             "Label".to_string(),
             ThemeWidget {
                 text: Some(ThemeText {
                     face: ThemeFace::new("./src/default_theme_resources/DejaVuSans.ttf", 0).unwrap(),
+                    bold_face: None,
+                    italic_face: None,
+                    bold_italic_face: None,
+                    fallback_faces: Vec::new(),
                     color: Rgba::new(Nu8(0), Nu8(0), Nu8(0), Nu8(255)),
                     highlight_bg_color: Rgba::new(Nu8(0), Nu8(120), Nu8(215), Nu8(255)),
                     highlight_text_color: Rgba::new(Nu8(255), Nu8(255), Nu8(255), Nu8(255)),
@@ -208,8 +347,10 @@ impl Default for Theme {
                     tab_size: 8,
                     justify: Align2::new(Align::Center, Align::Start),
                     margins: Margins::default(),
-                    line_wrap: LineWrap::Normal
+                    line_wrap: LineWrap::Normal,
+                    truncation: Truncation::None
                 }),
+                shape: None,
                 image: None
             }
         );
@@ -218,6 +359,26 @@ impl Default for Theme {
     }
 }
 
+impl ThemeWidget {
+    /// The minimum size the widget's shape and image can be drawn at without being distorted.
+    pub fn min_size(&self) -> DimsBox<Point2<i32>> {
+        let shape_min = self.shape.map(|s| s.min_size()).unwrap_or(DimsBox::new2(0, 0));
+        let image_min = self.image.as_ref().map(|i| i.min_size()).unwrap_or(DimsBox::new2(0, 0));
+        DimsBox::new2(
+            shape_min.width().max(image_min.width()),
+            shape_min.height().max(image_min.height())
+        )
+    }
+}
+
+impl ThemeShape {
+    /// The smallest size the shape can be drawn at while still fitting its border and corners.
+    pub fn min_size(&self) -> DimsBox<Point2<i32>> {
+        let edge = self.border_width.max(self.corner_radius) as i32 * 2;
+        DimsBox::new2(edge, edge)
+    }
+}
+
 impl Image {
     pub fn min_size(&self) -> DimsBox<Point2<i32>> {
         match self.rescale {
@@ -1,5 +1,5 @@
 use widgets::{Contents, ContentsInner};
-use core::event::{EventOps, WidgetEvent, InputState};
+use core::event::{EventOps, EventContext, WidgetEvent, InputState};
 use core::tree::{WidgetIdent, UpdateTag, WidgetSubtrait, WidgetSubtraitMut, Widget};
 use core::render::{FrameRectStack, Theme};
 use core::popup::ChildPopupsMut;
@@ -46,6 +46,9 @@ pub struct Button<H: ButtonHandler> {
     handler: H,
     contents: ContentsInner,
     waiting_for_mouseover: bool,
+    /// The measured minimum size of the contents along with the generation of the theme it was
+    /// measured with, or `None` if the contents need to be measured.
+    contents_size: Option<(usize, DimsBox<Point2<i32>>)>,
     size_bounds: SizeBounds
 }
 
@@ -58,6 +61,7 @@ impl<H: ButtonHandler> Button<H> {
             handler,
             contents: contents.to_inner(),
             waiting_for_mouseover: false,
+            contents_size: None,
             size_bounds: SizeBounds::default()
         }
     }
@@ -68,8 +72,19 @@ impl<H: ButtonHandler> Button<H> {
 
     pub fn contents_mut(&mut self) -> Contents<&mut String> {
         self.update_tag.mark_render_self();
+        self.contents_size = None;
         self.contents.borrow_mut()
     }
+
+    fn image_str(&self) -> &'static str {
+        match self.state {
+            ButtonState::Normal    => "Button::Normal",
+            ButtonState::Hover     => "Button::Hover",
+            ButtonState::Clicked   => "Button::Clicked",
+            // ButtonState::Disabled  => "Button::Disabled",
+            // ButtonState::Defaulted => "Button::Defaulted"
+        }
+    }
 }
 
 impl<F, H> Widget<H::Action, F> for Button<H>
@@ -95,15 +110,25 @@ impl<F, H> Widget<H::Action, F> for Button<H>
         self.size_bounds
     }
 
-    fn render(&mut self, frame: &mut FrameRectStack<F>) {
-        let image_str = match self.state {
-            ButtonState::Normal    => "Button::Normal",
-            ButtonState::Hover     => "Button::Hover",
-            ButtonState::Clicked   => "Button::Clicked",
-            // ButtonState::Disabled  => "Button::Disabled",
-            // ButtonState::Defaulted => "Button::Defaulted"
+    fn update_size_bounds(&mut self, theme: &F::Theme) {
+        let image_str = self.image_str();
+        let contents_size = match self.contents_size {
+            Some((generation, size)) if generation == theme.generation() => size,
+            _ => {
+                let size = self.contents.measure(theme, image_str);
+                self.contents_size = Some((theme.generation(), size));
+                size
+            }
         };
 
+        self.size_bounds.min = theme.widget_theme(image_str).min_size();
+        self.size_bounds.min.dims.x += contents_size.width();
+        self.size_bounds.min.dims.y += contents_size.height();
+    }
+
+    fn render(&mut self, frame: &mut FrameRectStack<F>) {
+        let image_str = self.image_str();
+
         frame.upload_primitives(ArrayVec::from([
             ThemedPrim {
                 theme_path: image_str,
@@ -119,11 +144,6 @@ impl<F, H> Widget<H::Action, F> for Button<H>
             },
             self.contents.to_prim(image_str)
         ]).into_iter());
-
-        self.size_bounds.min = frame.theme().widget_theme(image_str).image.map(|i| i.min_size()).unwrap_or(DimsBox::new2(0, 0));
-        let render_string_min = self.contents.min_size(frame.theme());
-        self.size_bounds.min.dims.x += render_string_min.width();
-        self.size_bounds.min.dims.y += render_string_min.height();
     }
 
     fn register_timers(&self, register: &mut TimerRegister) {
@@ -132,7 +152,7 @@ impl<F, H> Widget<H::Action, F> for Button<H>
         }
     }
 
-    fn on_widget_event(&mut self, event: WidgetEvent, input_state: InputState, popups_opt: Option<ChildPopupsMut<H::Action, F>>, bubble_source: &[WidgetIdent]) -> EventOps<H::Action, F> {
+    fn on_widget_event(&mut self, event: WidgetEvent, input_state: InputState, _: &mut EventContext, popups_opt: Option<ChildPopupsMut<H::Action, F>>, bubble_source: &[WidgetIdent]) -> EventOps<H::Action, F> {
         use self::WidgetEvent::*;
 
         let (mut action, focus) = (None, None);
@@ -188,7 +208,11 @@ impl<F, H> Widget<H::Action, F> for Button<H>
                 MouseExitChild{..} => unreachable!(),
                 GainFocus => ButtonState::Hover,
                 LoseFocus => ButtonState::Normal,
+                MouseScrollLines(_) |
+                MouseScrollPx(_)    |
                 Char(_)     |
+                ImePreedit{..} |
+                ImeCommit(_) |
                 KeyDown(..) |
                 KeyUp(..)  => self.state,
                 Timer{name: "mouseover_text", times_triggered: 1, ..} => {
@@ -211,7 +235,9 @@ impl<F, H> Widget<H::Action, F> for Button<H>
             };
 
             if new_state != self.state {
+                // Each state has its own theme, which the contents may measure differently in.
                 self.update_tag.mark_render_self();
+                self.contents_size = None;
                 self.state = new_state;
             }
         }
@@ -222,6 +248,7 @@ impl<F, H> Widget<H::Action, F> for Button<H>
             bubble: true,
             cursor_pos: None,
             cursor_icon: None,
+            ime_caret_rect: None,
             popup
         }
     }
@@ -0,0 +1,99 @@
+use core::event::{EventOps, EventContext, WidgetEvent, InputState};
+use core::tree::{WidgetIdent, UpdateTag, WidgetSubtrait, WidgetSubtraitMut, Widget};
+use core::render::FrameRectStack;
+use core::popup::ChildPopupsMut;
+
+use cgmath::Point2;
+use cgmath_geometry::BoundBox;
+
+use gl_render::{ThemedPrim, PrimFrame, RelPoint, Prim};
+
+pub use gl_render::{DrawCommand, Path, PathSegment, Stroke};
+
+/// A widget that draws lines, shapes, text, and images from a list of `DrawCommand`s.
+///
+/// Unlike `DirectRender`, the commands get drawn by the renderer, so they work with any renderer
+/// that supports them.
+#[derive(Debug, Clone)]
+pub struct Canvas {
+    update_tag: UpdateTag,
+    bounds: BoundBox<Point2<i32>>,
+    commands: Vec<DrawCommand>
+}
+
+impl Canvas {
+    pub fn new(commands: Vec<DrawCommand>) -> Canvas {
+        Canvas {
+            update_tag: UpdateTag::new(),
+            bounds: BoundBox::new2(0, 0, 0, 0),
+            commands
+        }
+    }
+
+    /// Get the commands drawn by the canvas, in the order they're drawn.
+    pub fn commands(&self) -> &[DrawCommand] {
+        &self.commands
+    }
+
+    pub fn commands_mut(&mut self) -> &mut Vec<DrawCommand> {
+        self.update_tag.mark_render_self();
+        &mut self.commands
+    }
+}
+
+impl<A, F> Widget<A, F> for Canvas
+    where F: PrimFrame
+{
+    #[inline]
+    fn update_tag(&self) -> &UpdateTag {
+        &self.update_tag
+    }
+
+    #[inline]
+    fn rect(&self) -> BoundBox<Point2<i32>> {
+        self.bounds
+    }
+
+    #[inline]
+    fn rect_mut(&mut self) -> &mut BoundBox<Point2<i32>> {
+        &mut self.bounds
+    }
+
+    fn render(&mut self, frame: &mut FrameRectStack<F>) {
+        frame.upload_primitives(Some(ThemedPrim {
+            theme_path: "Canvas",
+            min: Point2::new(
+                RelPoint::new(-1.0, 0),
+                RelPoint::new(-1.0, 0),
+            ),
+            max: Point2::new(
+                RelPoint::new( 1.0, 0),
+                RelPoint::new( 1.0, 0)
+            ),
+            prim: Prim::Canvas(&mut self.commands)
+        }).into_iter());
+    }
+
+    #[inline]
+    fn on_widget_event(&mut self, _: WidgetEvent, _: InputState, _: &mut EventContext, _: Option<ChildPopupsMut<A, F>>, _: &[WidgetIdent]) -> EventOps<A, F> {
+        EventOps {
+            action: None,
+            focus: None,
+            bubble: true,
+            cursor_pos: None,
+            cursor_icon: None,
+            ime_caret_rect: None,
+            popup: None
+        }
+    }
+
+    #[inline]
+    fn subtrait(&self) -> WidgetSubtrait<A, F> {
+        WidgetSubtrait::Widget(self)
+    }
+
+    #[inline]
+    fn subtrait_mut(&mut self) -> WidgetSubtraitMut<A, F> {
+        WidgetSubtraitMut::Widget(self)
+    }
+}
@@ -1,4 +1,4 @@
-use core::event::{EventOps, WidgetEvent, InputState};
+use core::event::{EventOps, EventContext, WidgetEvent, InputState};
 use core::tree::{WidgetIdent, UpdateTag, WidgetSubtrait, WidgetSubtraitMut, Widget, };
 use core::render::FrameRectStack;
 use core::popup::ChildPopupsMut;
@@ -8,8 +8,6 @@ use cgmath_geometry::BoundBox;
 
 use gl_render::{ThemedPrim, PrimFrame, RelPoint, Prim};
 
-use std::mem;
-
 pub struct DirectRender<R>
     where R: DirectRenderState
 {
@@ -74,18 +72,19 @@ impl<A, F, R> Widget<A, F> for DirectRender<R>
                 RelPoint::new( 1.0, 0),
                 RelPoint::new( 1.0, 0)
             ),
-            prim: unsafe{ Prim::DirectRender(mem::transmute((&mut draw_fn) as &mut FnMut(&mut R::RenderType))) }
+            prim: Prim::DirectRender(&mut draw_fn)
         }).into_iter());
     }
 
     #[inline]
-    fn on_widget_event(&mut self, _: WidgetEvent, _: InputState, _: Option<ChildPopupsMut<A, F>>, _: &[WidgetIdent]) -> EventOps<A, F> {
+    fn on_widget_event(&mut self, _: WidgetEvent, _: InputState, _: &mut EventContext, _: Option<ChildPopupsMut<A, F>>, _: &[WidgetIdent]) -> EventOps<A, F> {
         EventOps {
             action: None,
             focus: None,
             bubble: true,
             cursor_pos: None,
             cursor_icon: None,
+            ime_caret_rect: None,
             popup: None
         }
     }
@@ -1,37 +1,93 @@
-use core::event::{EventOps, WidgetEvent, InputState, FocusChange};
+use core::event::{EventOps, EventContext, WidgetEvent, InputState, FocusChange};
+use core::clipboard::ClipboardKind;
 use core::tree::{WidgetIdent, UpdateTag, WidgetSubtrait, WidgetSubtraitMut, Widget};
 use core::render::{FrameRectStack, Theme};
 use core::popup::ChildPopupsMut;
 use core::timer::TimerRegister;
 
 use cgmath::Point2;
-use cgmath_geometry::{BoundBox, DimsBox, GeoBox, Segment};
+use cgmath_geometry::{BoundBox, GeoBox, Segment};
 use dct::layout::SizeBounds;
 use dct::cursor::CursorIcon;
 use dct::buttons::{Key, ModifierKeys};
 
 use gl_render::{ThemedPrim, PrimFrame, RenderString, EditString, RelPoint, Prim};
 
-use std::time::Duration;
+use std::borrow::Cow;
+use std::time::{Duration, Instant};
 
-use clipboard::{ClipboardContext, ClipboardProvider};
 use arrayvec::ArrayVec;
 
+/// Maximum time, in milliseconds, between clicks for them to count as a double- or triple-click.
+const MULTI_CLICK_TIME_MS: u64 = 500;
+/// Maximum distance, in pixels, the mouse can move between clicks of a multi-click.
+const MULTI_CLICK_DIST: i32 = 4;
+
+pub trait EditBoxHandler {
+    type Action: 'static;
+
+    /// Called whenever the user changes the edit box's text.
+    fn on_text_changed(&mut self, _text: &str) -> Option<Self::Action> {
+        None
+    }
+
+    /// Called when the user presses `Enter` while the edit box has keyboard focus. Multiline edit
+    /// boxes submit on `Ctrl+Enter` instead.
+    fn on_submit(&mut self, _text: &str) -> Option<Self::Action> {
+        None
+    }
+
+    /// Called when the edit box loses keyboard focus.
+    fn on_commit(&mut self, _text: &str) -> Option<Self::Action> {
+        None
+    }
+
+    /// Filter a typed character before it gets inserted into `text`. Returning `None` rejects the
+    /// character, and returning a different character inserts that character instead.
+    fn filter_char(&mut self, c: char, _text: &str) -> Option<char> {
+        Some(c)
+    }
+
+    /// Filter pasted text before it gets inserted into `text`. Returning `None` rejects the paste
+    /// entirely.
+    fn filter_str<'a>(&mut self, s: &'a str, _text: &str) -> Option<Cow<'a, str>> {
+        Some(Cow::Borrowed(s))
+    }
+}
+
+impl<A: 'static + Clone> EditBoxHandler for Option<A> {
+    type Action = A;
+
+    fn on_submit(&mut self, _: &str) -> Option<Self::Action> {
+        self.clone()
+    }
+}
+
 #[derive(Debug, Clone)]
-pub struct EditBox {
+pub struct EditBox<H: EditBoxHandler> {
     update_tag: UpdateTag,
     bounds: BoundBox<Point2<i32>>,
     string: EditString,
-    size_bounds: SizeBounds
+    placeholder: RenderString,
+    handler: H,
+    size_bounds: SizeBounds,
+    last_click: Option<(Instant, Point2<i32>)>,
+    click_count: u32,
+    multiline: bool
 }
 
-impl EditBox {
-    pub fn new(string: String) -> EditBox {
+impl<H: EditBoxHandler> EditBox<H> {
+    pub fn new(string: String, handler: H) -> EditBox<H> {
         EditBox {
             update_tag: UpdateTag::new(),
             bounds: BoundBox::new2(0, 0, 0, 0),
             string: EditString::new(RenderString::new(string)),
-            size_bounds: SizeBounds::default()
+            placeholder: RenderString::new(String::new()),
+            handler,
+            size_bounds: SizeBounds::default(),
+            last_click: None,
+            click_count: 0,
+            multiline: false
         }
     }
 
@@ -41,12 +97,59 @@ impl EditBox {
 
     pub fn string_mut(&mut self) -> &mut String {
         self.update_tag.mark_render_self();
+        self.string.clear_preedit();
+        self.string.clear_history();
         self.string.render_string.string_mut()
     }
+
+    /// Get the character drawn in place of each grapheme, if the edit box is masked.
+    pub fn mask(&self) -> Option<char> {
+        self.string.mask()
+    }
+
+    /// Draw `mask` in place of each grapheme, as is done in password fields. Copying and cutting
+    /// text is disabled while the edit box is masked.
+    pub fn set_mask(&mut self, mask: Option<char>) {
+        self.update_tag.mark_render_self();
+        self.string.set_mask(mask);
+    }
+
+    /// Get the text drawn when the edit box is empty and doesn't have keyboard focus.
+    pub fn placeholder(&self) -> &str {
+        self.placeholder.string()
+    }
+
+    pub fn placeholder_mut(&mut self) -> &mut String {
+        self.update_tag.mark_render_self();
+        self.placeholder.string_mut()
+    }
+
+    /// Get the maximum number of graphemes the user can enter.
+    pub fn max_len(&self) -> Option<usize> {
+        self.string.max_len()
+    }
+
+    /// Set the maximum number of graphemes the user can enter. This doesn't truncate the existing
+    /// string.
+    pub fn set_max_len(&mut self, max_len: Option<usize>) {
+        self.string.set_max_len(max_len);
+    }
+
+    /// Whether pressing `Enter` inserts a line break rather than submitting the edit box.
+    pub fn multiline(&self) -> bool {
+        self.multiline
+    }
+
+    /// Set whether pressing `Enter` inserts a line break. Multiline edit boxes submit on
+    /// `Ctrl+Enter`.
+    pub fn set_multiline(&mut self, multiline: bool) {
+        self.multiline = multiline;
+    }
 }
 
-impl<A, F> Widget<A, F> for EditBox
-    where F: PrimFrame
+impl<F, H> Widget<H::Action, F> for EditBox<H>
+    where F: PrimFrame,
+          H: EditBoxHandler
 {
     #[inline]
     fn update_tag(&self) -> &UpdateTag {
@@ -68,10 +171,19 @@ impl<A, F> Widget<A, F> for EditBox
         self.size_bounds
     }
 
+    fn update_size_bounds(&mut self, theme: &F::Theme) {
+        let widget_theme = theme.widget_theme("EditBox");
+        self.size_bounds.min = widget_theme.min_size();
+        if let Some(ref text_style) = widget_theme.text {
+            self.size_bounds.min.dims.y += theme.text_metrics().line_height(text_style);
+        }
+    }
+
     fn render(&mut self, frame: &mut FrameRectStack<F>) {
-        frame.upload_primitives(ArrayVec::from([
-            ThemedPrim {
-                theme_path: "EditBox",
+        let draw_placeholder = self.string.render_string.string().len() == 0 && !self.update_tag.has_keyboard_focus();
+        let string_prim = match draw_placeholder {
+            true => ThemedPrim {
+                theme_path: "EditBox::Placeholder",
                 min: Point2::new(
                     RelPoint::new(-1.0, 0),
                     RelPoint::new(-1.0, 0),
@@ -80,9 +192,9 @@ impl<A, F> Widget<A, F> for EditBox
                     RelPoint::new( 1.0, 0),
                     RelPoint::new( 1.0, 0)
                 ),
-                prim: Prim::Image
+                prim: Prim::String(&mut self.placeholder)
             },
-            ThemedPrim {
+            false => ThemedPrim {
                 theme_path: "EditBox",
                 min: Point2::new(
                     RelPoint::new(-1.0, 0),
@@ -94,64 +206,132 @@ impl<A, F> Widget<A, F> for EditBox
                 ),
                 prim: Prim::EditString(&mut self.string)
             }
+        };
+        frame.upload_primitives(ArrayVec::from([
+            ThemedPrim {
+                theme_path: "EditBox",
+                min: Point2::new(
+                    RelPoint::new(-1.0, 0),
+                    RelPoint::new(-1.0, 0),
+                ),
+                max: Point2::new(
+                    RelPoint::new( 1.0, 0),
+                    RelPoint::new( 1.0, 0)
+                ),
+                prim: Prim::Image
+            },
+            string_prim
         ]).into_iter());
-
-        self.size_bounds.min = frame.theme().widget_theme("EditBox").image.map(|i| i.min_size()).unwrap_or(DimsBox::new2(0, 0));
-        let render_string_min = self.string.render_string.min_size();
-        self.size_bounds.min.dims.y += render_string_min.height();
     }
 
-    fn on_widget_event(&mut self, event: WidgetEvent, _: InputState, _: Option<ChildPopupsMut<A, F>>, _: &[WidgetIdent]) -> EventOps<A, F> {
+    fn on_widget_event(&mut self, event: WidgetEvent, _: InputState, context: &mut EventContext, _: Option<ChildPopupsMut<H::Action, F>>, _: &[WidgetIdent]) -> EventOps<H::Action, F> {
         use self::WidgetEvent::*;
         use dct::buttons::MouseButton;
 
         let allow_char = |c| match c {
-            '\t' |
+            // Line breaks get inserted by the `Enter` key handler, so the characters it produces
+            // get ignored.
             '\r' |
-            '\n' => true,
+            '\n' => false,
+            '\t' => true,
             _ => !c.is_control()
         };
+        let mut action = None;
         let mut focus = None;
         let mut cursor_icon = None;
+        let mut text_changed = false;
+        let old_highlight_range = self.string.highlight_range();
+        let old_cursor_rect = self.string.cursor_rect();
+
+        let report_caret = match event {
+            KeyDown(..) |
+            Char(_) |
+            ImePreedit{..} |
+            ImeCommit(_) |
+            GainFocus |
+            MouseDown{in_widget: true, ..} => true,
+            _ => false
+        };
+        // Cancel the IME composition before doing anything that could edit or select the string.
+        // Losing focus commits the composition instead.
+        match event {
+            ImePreedit{..} |
+            LoseFocus |
+            MouseDown{in_widget: false, ..} |
+            Timer{..} |
+            MouseMove{..} |
+            MouseEnter{..} |
+            MouseExit{..} |
+            MouseEnterChild{..} |
+            MouseExitChild{..} => (),
+            _ => self.string.clear_preedit()
+        }
+
         match event {
             KeyDown(key, modifiers) => loop {
                 let jump_to_word_boundaries = modifiers.contains(ModifierKeys::CTRL);
                 match (key, modifiers) {
-                    (Key::LArrow, _) => self.string.move_cursor_horizontal(
+                    (Key::LArrow, _) => self.string.move_cursor_visual(
                         -1,
                         jump_to_word_boundaries,
                         modifiers.contains(ModifierKeys::SHIFT)
                     ),
-                    (Key::RArrow, _) => self.string.move_cursor_horizontal(
+                    (Key::RArrow, _) => self.string.move_cursor_visual(
                         1,
                         jump_to_word_boundaries,
                         modifiers.contains(ModifierKeys::SHIFT)
                     ),
+                    (Key::Home, _) if modifiers.contains(ModifierKeys::CTRL) =>
+                        self.string.move_cursor_string_boundary(-1, modifiers.contains(ModifierKeys::SHIFT)),
+                    (Key::End, _) if modifiers.contains(ModifierKeys::CTRL) =>
+                        self.string.move_cursor_string_boundary(1, modifiers.contains(ModifierKeys::SHIFT)),
+                    (Key::Home, _) => self.string.move_cursor_line_boundary(-1, modifiers.contains(ModifierKeys::SHIFT)),
+                    (Key::End, _) => self.string.move_cursor_line_boundary(1, modifiers.contains(ModifierKeys::SHIFT)),
                     (Key::UArrow, _) => self.string.move_cursor_vertical(-1, modifiers.contains(ModifierKeys::SHIFT)),
                     (Key::DArrow, _) => self.string.move_cursor_vertical(1, modifiers.contains(ModifierKeys::SHIFT)),
                     (Key::A, ModifierKeys::CTRL) => self.string.select_all(),
-                    (Key::C, ModifierKeys::CTRL) => {
-                        if let Ok(mut clipboard) = ClipboardContext::new() {
-                            let select_range = self.string.highlight_range();
-                            clipboard.set_contents(self.string.render_string.string()[select_range].to_string()).ok();
-                        }
+                    (Key::C, ModifierKeys::CTRL) if self.string.mask().is_none() => {
+                        let select_range = self.string.highlight_range();
+                        context.clipboard().set_contents(ClipboardKind::Clipboard, self.string.render_string.string()[select_range].to_string());
                     },
                     (Key::V, ModifierKeys::CTRL) => {
-                        if let Ok(clipboard_conents) = ClipboardContext::new().and_then(|mut c| c.get_contents()) {
-                            self.string.insert_str(&clipboard_conents);
+                        if let Some(clipboard_contents) = context.clipboard().get_contents(ClipboardKind::Clipboard) {
+                            let filtered = self.handler.filter_str(&clipboard_contents, self.string.render_string.string());
+                            if let Some(filtered) = filtered {
+                                text_changed = self.string.insert_str(&filtered);
+                            }
                         }
                     },
-                    (Key::X, ModifierKeys::CTRL) => {
-                        if let Ok(mut clipboard) = ClipboardContext::new() {
-                            let highlight_range = self.string.highlight_range();
-                            clipboard.set_contents(self.string.render_string.string()[highlight_range.clone()].to_string()).ok();
-                            if highlight_range.len() > 0 {
-                                self.string.delete_chars(1, false);
-                            }
+                    (Key::X, ModifierKeys::CTRL) if self.string.mask().is_none() => {
+                        let highlight_range = self.string.highlight_range();
+                        context.clipboard().set_contents(ClipboardKind::Clipboard, self.string.render_string.string()[highlight_range.clone()].to_string());
+                        if highlight_range.len() > 0 {
+                            self.string.delete_chars(1, false);
+                            text_changed = true;
                         }
                     },
-                    (Key::Back, _) => self.string.delete_chars(-1, jump_to_word_boundaries),
-                    (Key::Delete, _) => self.string.delete_chars(1, jump_to_word_boundaries),
+                    (Key::Z, ModifierKeys::CTRL) => text_changed = self.string.undo(),
+                    (Key::Y, ModifierKeys::CTRL) => text_changed = self.string.redo(),
+                    (Key::Z, _) if modifiers == ModifierKeys::CTRL | ModifierKeys::SHIFT => text_changed = self.string.redo(),
+                    (Key::Back, _) => {
+                        let old_len = self.string.render_string.string().len();
+                        self.string.delete_chars(-1, jump_to_word_boundaries);
+                        text_changed = old_len != self.string.render_string.string().len();
+                    },
+                    (Key::Delete, _) => {
+                        let old_len = self.string.render_string.string().len();
+                        self.string.delete_chars(1, jump_to_word_boundaries);
+                        text_changed = old_len != self.string.render_string.string().len();
+                    },
+                    (Key::Enter, _) if self.multiline && !modifiers.contains(ModifierKeys::CTRL) => {
+                        if let Some(c) = self.handler.filter_char('\n', self.string.render_string.string()) {
+                            text_changed = self.string.insert_char(c);
+                        }
+                    },
+                    (Key::Enter, _) => {
+                        action = self.handler.on_submit(self.string.render_string.string());
+                        break;
+                    },
                     _ => break
                 }
                 self.update_tag
@@ -160,15 +340,59 @@ impl<A, F> Widget<A, F> for EditBox
                 break;
             },
             Char(c) if allow_char(c) => {
-                self.string.insert_char(c);
+                if let Some(c) = self.handler.filter_char(c, self.string.render_string.string()) {
+                    text_changed = self.string.insert_char(c);
+                    self.update_tag
+                        .mark_render_self()
+                        .mark_update_timer();
+                }
+            }
+            ImePreedit{text, cursor} => {
+                self.string.set_preedit(text, cursor);
                 self.update_tag
                     .mark_render_self()
                     .mark_update_timer();
-            }
+            },
+            ImeCommit(text) => {
+                if let Some(filtered) = self.handler.filter_str(text, self.string.render_string.string()) {
+                    text_changed = self.string.insert_str(&filtered);
+                }
+                self.update_tag
+                    .mark_render_self()
+                    .mark_update_timer();
+            },
+            MouseDown{in_widget: true, button: MouseButton::Middle, pos} => {
+                focus = Some(FocusChange::Take);
+                self.string.select_on_line(Segment::new(pos, pos));
+                if let Some(primary_contents) = context.clipboard().get_contents(ClipboardKind::Primary) {
+                    let filtered = self.handler.filter_str(&primary_contents, self.string.render_string.string());
+                    if let Some(filtered) = filtered {
+                        text_changed = self.string.insert_str(&filtered);
+                    }
+                }
+                self.update_tag
+                    .mark_render_self()
+                    .mark_update_timer();
+            },
             MouseDown{in_widget: true, button, pos} => {
                 focus = Some(FocusChange::Take);
                 if button == MouseButton::Left {
-                    self.string.select_on_line(Segment::new(pos, pos));
+                    let now = Instant::now();
+                    self.click_count = match self.last_click {
+                        Some((click_time, click_pos)) if
+                            now - click_time <= Duration::from_millis(MULTI_CLICK_TIME_MS) &&
+                            (click_pos.x - pos.x).abs() <= MULTI_CLICK_DIST &&
+                            (click_pos.y - pos.y).abs() <= MULTI_CLICK_DIST
+                            => self.click_count % 3 + 1,
+                        _ => 1
+                    };
+                    self.last_click = Some((now, pos));
+
+                    match self.click_count {
+                        1 => self.string.select_on_line(Segment::new(pos, pos)),
+                        2 => self.string.select_word_at(pos),
+                        _ => self.string.select_line_at(pos)
+                    }
                     self.update_tag
                         .mark_render_self()
                         .mark_update_timer();
@@ -185,17 +409,32 @@ impl<A, F> Widget<A, F> for EditBox
                     .mark_update_timer();
             },
             MouseMove{new_pos, buttons_down_in_widget, ..} => {
-                if let Some(down) = buttons_down_in_widget.iter().find(|d| d.button == MouseButton::Left) {
+                let down = buttons_down_in_widget.iter().find(|d| d.button == MouseButton::Left);
+                if let (Some(down), 1) = (down, self.click_count) {
                     self.string.select_on_line(Segment::new(down.down_pos, new_pos));
                     self.update_tag.mark_render_self();
                 }
             },
             MouseEnter{..} => cursor_icon = Some(CursorIcon::Text),
             MouseExit{..} => cursor_icon = Some(CursorIcon::default()),
-            GainFocus  |
+            GainFocus => {
+                self.string.deselect_all();
+                self.update_tag
+                    .mark_render_self()
+                    .mark_update_timer();
+            },
             LoseFocus => {
+                if let Some(preedit) = self.string.preedit().map(|p| p.to_string()) {
+                    self.string.clear_preedit();
+                    if let Some(filtered) = self.handler.filter_str(&preedit, self.string.render_string.string()) {
+                        text_changed = self.string.insert_str(&filtered);
+                    }
+                }
                 self.string.deselect_all();
-                self.update_tag.mark_update_timer();
+                self.update_tag
+                    .mark_render_self()
+                    .mark_update_timer();
+                action = self.handler.on_commit(self.string.render_string.string());
             },
             Timer{name: "cursor_flash", times_triggered, ..} => {
                 self.string.draw_cursor = times_triggered % 2 == 0;
@@ -203,12 +442,24 @@ impl<A, F> Widget<A, F> for EditBox
             },
             _ => ()
         };
+        let highlight_range = self.string.highlight_range();
+        let is_composing = self.string.preedit_range().len() > 0;
+        if highlight_range != old_highlight_range && highlight_range.len() > 0 && self.string.mask().is_none() && !is_composing {
+            context.clipboard().set_contents(ClipboardKind::Primary, self.string.render_string.string()[highlight_range].to_string());
+        }
+        if text_changed {
+            action = action.or_else(|| self.handler.on_text_changed(self.string.render_string.string()));
+        }
         EventOps {
-            action: None,
+            action,
             focus,
             bubble: true,
             cursor_pos: None,
             cursor_icon,
+            ime_caret_rect: match report_caret {
+                true => self.string.cursor_rect().or(old_cursor_rect),
+                false => None
+            },
             popup: None
         }
     }
@@ -220,12 +471,12 @@ impl<A, F> Widget<A, F> for EditBox
     }
 
     #[inline]
-    fn subtrait(&self) -> WidgetSubtrait<A, F> {
+    fn subtrait(&self) -> WidgetSubtrait<H::Action, F> {
         WidgetSubtrait::Widget(self)
     }
 
     #[inline]
-    fn subtrait_mut(&mut self) -> WidgetSubtraitMut<A, F> {
+    fn subtrait_mut(&mut self) -> WidgetSubtraitMut<H::Action, F> {
         WidgetSubtraitMut::Widget(self)
     }
 }
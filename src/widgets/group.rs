@@ -1,5 +1,5 @@
 use core::LoopFlow;
-use core::event::{EventOps, WidgetEvent, InputState};
+use core::event::{EventOps, EventContext, WidgetEvent, InputState};
 use core::tree::{WidgetIdent, UpdateTag, WidgetSummary, WidgetSubtrait, WidgetSubtraitMut, Widget, Parent, OnFocus};
 use core::render::FrameRectStack;
 use core::popup::ChildPopupsMut;
@@ -92,13 +92,14 @@ impl<A, F, C, L> Widget<A, F> for Group<C, L>
     }
 
     #[inline]
-    fn on_widget_event(&mut self, _: WidgetEvent, _: InputState, _: Option<ChildPopupsMut<A, F>>, _: &[WidgetIdent]) -> EventOps<A, F> {
+    fn on_widget_event(&mut self, _: WidgetEvent, _: InputState, _: &mut EventContext, _: Option<ChildPopupsMut<A, F>>, _: &[WidgetIdent]) -> EventOps<A, F> {
         EventOps {
             action: None,
             focus: None,
             bubble: true,
             cursor_pos: None,
             cursor_icon: None,
+            ime_caret_rect: None,
             popup: None
         }
     }
@@ -1,30 +1,95 @@
 use widgets::{Contents, ContentsInner};
-use core::event::{EventOps, WidgetEvent, InputState};
+use core::clipboard::ClipboardKind;
+use core::event::{EventOps, EventContext, WidgetEvent, InputState, FocusChange};
 use core::tree::{WidgetIdent, UpdateTag, WidgetSubtrait, WidgetSubtraitMut, Widget};
 use core::render::FrameRectStack;
 use core::popup::ChildPopupsMut;
 
 use cgmath::Point2;
-use cgmath_geometry::{BoundBox, DimsBox};
+use cgmath_geometry::{BoundBox, DimsBox, Segment};
 use dct::layout::SizeBounds;
+use dct::cursor::CursorIcon;
+use dct::buttons::{Key, ModifierKeys, MouseButton};
 
-use gl_render::PrimFrame;
+use gl_render::{PrimFrame, EditString, TextSpan};
+use theme::SpanStyle;
+use theme::color::{Rgba, Nu8};
+
+use std::mem;
+use std::ops::Range;
+
+/// Handles clicks on a label's links.
+///
+/// Unlike most handlers, this is generic over the action type, so that a `Label` without links
+/// can be placed in a container with any action type.
+pub trait LabelHandler<A> {
+    /// Called when the user clicks or activates the link with the given target.
+    fn on_link_click(&mut self, _target: &str) -> Option<A> {
+        None
+    }
+}
+
+impl<A> LabelHandler<A> for () {}
+
+/// A clickable link within a label's text.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LabelLink {
+    /// The byte range of the link's text within the label's string.
+    pub range: Range<usize>,
+    /// The link target, which gets passed to the `LabelHandler` when the link is clicked.
+    pub target: String
+}
 
 #[derive(Debug, Clone)]
-pub struct Label {
+pub struct Label<H = ()> {
     update_tag: UpdateTag,
     bounds: BoundBox<Point2<i32>>,
     contents: ContentsInner,
-    min_size: DimsBox<Point2<i32>>
+    /// The measured minimum size of the contents, or `None` if the contents need to be measured.
+    min_size: Option<DimsBox<Point2<i32>>>,
+    handler: H,
+    links: Vec<LabelLink>,
+    link_style: SpanStyle,
+    link_hover_style: SpanStyle,
+    /// The link under the mouse cursor.
+    hover_link: Option<usize>,
+    /// The link selected by the keyboard.
+    focus_link: Option<usize>,
+    /// Whether the label gained keyboard focus from being clicked, rather than from `Tab`.
+    mouse_focus: bool,
+    spans_dirty: bool
 }
 
 impl Label {
     pub fn new(contents: Contents<String>) -> Label {
+        Label::with_handler(contents, ())
+    }
+}
+
+impl<H> Label<H> {
+    /// Create a label that passes link clicks to the given handler.
+    pub fn with_handler(contents: Contents<String>, handler: H) -> Label<H> {
         Label {
             update_tag: UpdateTag::new(),
             bounds: BoundBox::new2(0, 0, 0, 0),
             contents: contents.to_inner(),
-            min_size: DimsBox::new2(0, 0)
+            min_size: None,
+            handler,
+            links: Vec::new(),
+            link_style: SpanStyle {
+                color: Some(Rgba::new(Nu8(0), Nu8(102), Nu8(204), Nu8(255))),
+                underline: Some(true),
+                ..SpanStyle::default()
+            },
+            link_hover_style: SpanStyle {
+                color: Some(Rgba::new(Nu8(0), Nu8(140), Nu8(255), Nu8(255))),
+                underline: Some(true),
+                ..SpanStyle::default()
+            },
+            hover_link: None,
+            focus_link: None,
+            mouse_focus: false,
+            spans_dirty: false
         }
     }
 
@@ -34,12 +99,117 @@ impl Label {
 
     pub fn contents_mut(&mut self) -> Contents<&mut String> {
         self.update_tag.mark_render_self();
+        self.spans_dirty = true;
+        self.min_size = None;
         self.contents.borrow_mut()
     }
+
+    /// Get the links in the label's text. Links are ignored if the label contains an image.
+    pub fn links(&self) -> &[LabelLink] {
+        &self.links
+    }
+
+    /// Get a mutable reference to the label's links. Link ranges aren't adjusted when the
+    /// label's text changes.
+    pub fn links_mut(&mut self) -> &mut Vec<LabelLink> {
+        self.update_tag.mark_render_self();
+        self.spans_dirty = true;
+        self.min_size = None;
+        self.hover_link = None;
+        self.focus_link = None;
+        &mut self.links
+    }
+
+    /// Set the styles used to draw links normally, and when they're hovered over or selected with
+    /// the keyboard.
+    pub fn set_link_styles(&mut self, link_style: SpanStyle, link_hover_style: SpanStyle) {
+        self.update_tag.mark_render_self();
+        self.spans_dirty = true;
+        self.min_size = None;
+        self.link_style = link_style;
+        self.link_hover_style = link_hover_style;
+    }
+
+    /// Whether the user can select and copy the label's text.
+    pub fn selectable(&self) -> bool {
+        match self.contents {
+            ContentsInner::SelectableText(_) => true,
+            _ => false
+        }
+    }
+
+    /// Allow or disallow the user to select and copy the label's text with the mouse, `Ctrl+A`
+    /// and `Ctrl+C`. This has no effect if the label contains an image.
+    pub fn set_selectable(&mut self, selectable: bool) {
+        let contents = mem::replace(&mut self.contents, ContentsInner::Image(String::new()));
+        self.contents = match (contents, selectable) {
+            (ContentsInner::Text(s), true) => ContentsInner::SelectableText(EditString::new(s)),
+            (ContentsInner::SelectableText(s), false) => ContentsInner::Text(s.render_string),
+            (contents, _) => contents
+        };
+        self.update_tag.mark_render_self();
+    }
+
+    /// Get the index of the link drawn under the given point.
+    fn link_at(&self, pos: Point2<i32>) -> Option<usize> {
+        let str_index = self.contents.render_string()?.str_index_at(pos)?;
+        self.links.iter().position(|link| link.range.contains(str_index))
+    }
+
+    /// Get the cursor drawn while the mouse is over the label, but not over a link.
+    fn text_cursor_icon(&self) -> CursorIcon {
+        match self.selectable() {
+            true => CursorIcon::Text,
+            false => CursorIcon::default()
+        }
+    }
+
+    fn update_spans(&mut self) {
+        if let Some(s) = self.contents.render_string_mut() {
+            let (hover_link, focus_link) = (self.hover_link, self.focus_link);
+            let (link_style, link_hover_style) = (&self.link_style, &self.link_hover_style);
+
+            let spans = s.spans_mut();
+            spans.clear();
+            spans.extend(self.links.iter().enumerate().map(|(i, link)| TextSpan {
+                range: link.range.clone(),
+                style: match Some(i) == hover_link || Some(i) == focus_link {
+                    true => link_hover_style.clone(),
+                    false => link_style.clone()
+                }
+            }));
+        }
+        self.spans_dirty = false;
+    }
+
+    fn set_hover_link(&mut self, hover_link: Option<usize>) -> Option<CursorIcon> {
+        if hover_link == self.hover_link {
+            return None;
+        }
+        let was_hovering = self.hover_link.is_some();
+        self.hover_link = hover_link;
+        self.spans_dirty = true;
+        self.update_tag.mark_render_self();
+
+        match (was_hovering, hover_link.is_some()) {
+            (false, true) => Some(CursorIcon::Hand),
+            (true, false) => Some(self.text_cursor_icon()),
+            _ => None
+        }
+    }
+
+    fn set_focus_link(&mut self, focus_link: Option<usize>) {
+        if focus_link != self.focus_link {
+            self.focus_link = focus_link;
+            self.spans_dirty = true;
+            self.update_tag.mark_render_self();
+        }
+    }
 }
 
-impl<A, F> Widget<A, F> for Label
-    where F: PrimFrame
+impl<A, F, H> Widget<A, F> for Label<H>
+    where F: PrimFrame,
+          H: LabelHandler<A>
 {
     #[inline]
     fn update_tag(&self) -> &UpdateTag {
@@ -57,22 +227,160 @@ impl<A, F> Widget<A, F> for Label
     }
 
     fn size_bounds(&self) -> SizeBounds {
-        SizeBounds::new_min(self.min_size)
+        SizeBounds::new_min(self.min_size.unwrap_or(DimsBox::new2(0, 0)))
+    }
+
+    fn update_size_bounds(&mut self, theme: &F::Theme) {
+        // Spans can change the text's size, so they need to be up-to-date before measuring.
+        if self.spans_dirty {
+            self.update_spans();
+        }
+        if self.min_size.is_none() {
+            self.min_size = Some(self.contents.measure(theme, "Label"));
+        }
     }
 
     fn render(&mut self, frame: &mut FrameRectStack<F>) {
+        if self.spans_dirty {
+            self.update_spans();
+        }
         frame.upload_primitives(Some(self.contents.to_prim("Label")).into_iter());
-        self.min_size = self.contents.min_size(frame.theme());
     }
 
-    #[inline]
-    fn on_widget_event(&mut self, _: WidgetEvent, _: InputState, _: Option<ChildPopupsMut<A, F>>, _: &[WidgetIdent]) -> EventOps<A, F> {
+    fn on_widget_event(&mut self, event: WidgetEvent, _: InputState, context: &mut EventContext, _: Option<ChildPopupsMut<A, F>>, bubble_source: &[WidgetIdent]) -> EventOps<A, F> {
+        use self::WidgetEvent::*;
+
+        let mut action = None;
+        let mut focus = None;
+        let mut cursor_icon = None;
+        let old_highlight_range = match self.contents {
+            ContentsInner::SelectableText(ref s) => s.highlight_range(),
+            _ => 0..0
+        };
+
+        if bubble_source.len() == 0 {
+            match event {
+                MouseEnter{enter_pos, ..} => {
+                    let hover_link = self.link_at(enter_pos);
+                    self.set_hover_link(hover_link);
+                    cursor_icon = Some(match hover_link {
+                        Some(_) => CursorIcon::Hand,
+                        None => self.text_cursor_icon()
+                    });
+                },
+                MouseMove{new_pos, in_widget, buttons_down_in_widget, ..} => {
+                    let hover_link = match in_widget {
+                        true => self.link_at(new_pos),
+                        false => None
+                    };
+                    cursor_icon = self.set_hover_link(hover_link);
+
+                    let down = buttons_down_in_widget.iter().find(|d| d.button == MouseButton::Left);
+                    if let (Some(down), &mut ContentsInner::SelectableText(ref mut s)) = (down, &mut self.contents) {
+                        s.select_on_line(Segment::new(down.down_pos, new_pos));
+                        self.update_tag.mark_render_self();
+                    }
+                },
+                MouseExit{..} => {
+                    cursor_icon = self.set_hover_link(None);
+                    if self.selectable() {
+                        cursor_icon = Some(CursorIcon::default());
+                    }
+                },
+                MouseDown{in_widget: true, button: MouseButton::Left, pos} => {
+                    if let ContentsInner::SelectableText(ref mut s) = self.contents {
+                        // Only clicks that give the label focus should stop `GainFocus` from
+                        // focusing the first link.
+                        if !self.update_tag.has_keyboard_focus() {
+                            focus = Some(FocusChange::Take);
+                            self.mouse_focus = true;
+                        }
+                        s.select_on_line(Segment::new(pos, pos));
+                        self.update_tag.mark_render_self();
+                    }
+                },
+                MouseDown{in_widget: false, ..} => {
+                    if self.selectable() {
+                        focus = Some(FocusChange::Remove);
+                    }
+                },
+                MouseUp{in_widget: true, pressed_in_widget: true, pos, down_pos, button: MouseButton::Left} => {
+                    let clicked_link = self.link_at(pos);
+                    if clicked_link.is_some() && clicked_link == self.link_at(down_pos) {
+                        let link = &self.links[clicked_link.unwrap()];
+                        action = self.handler.on_link_click(&link.target);
+                    }
+                },
+                GainFocus => {
+                    match (self.links.len(), self.mouse_focus) {
+                        (_, true) => (),
+                        // Skip over labels with nothing to focus on.
+                        (0, false) if !self.selectable() => focus = Some(FocusChange::Next),
+                        (0, false) => (),
+                        (_, false) => self.set_focus_link(Some(0))
+                    }
+                    self.mouse_focus = false;
+                },
+                LoseFocus => {
+                    self.mouse_focus = false;
+                    self.set_focus_link(None);
+                    if let ContentsInner::SelectableText(ref mut s) = self.contents {
+                        s.deselect_all();
+                        self.update_tag.mark_render_self();
+                    }
+                },
+                KeyDown(Key::Tab, modifiers) => {
+                    let backwards = modifiers.contains(ModifierKeys::SHIFT);
+                    let next_link = match (backwards, self.focus_link) {
+                        (false, Some(i)) if i + 1 < self.links.len() => Some(i + 1),
+                        (true, Some(i)) if i > 0 => Some(i - 1),
+                        _ => None
+                    };
+                    match (next_link, backwards) {
+                        (Some(_), _) => self.set_focus_link(next_link),
+                        (None, false) => focus = Some(FocusChange::Next),
+                        (None, true) => focus = Some(FocusChange::Prev)
+                    }
+                },
+                KeyDown(Key::Enter, _) |
+                KeyDown(Key::Space, _) => {
+                    let links = &self.links;
+                    if let Some(link) = self.focus_link.and_then(|i| links.get(i)) {
+                        action = self.handler.on_link_click(&link.target);
+                    }
+                },
+                KeyDown(Key::A, ModifierKeys::CTRL) => {
+                    if let ContentsInner::SelectableText(ref mut s) = self.contents {
+                        s.select_all();
+                        self.update_tag.mark_render_self();
+                    }
+                },
+                KeyDown(Key::C, ModifierKeys::CTRL) => {
+                    if let ContentsInner::SelectableText(ref s) = self.contents {
+                        let highlight_range = s.highlight_range();
+                        if highlight_range.len() > 0 {
+                            context.clipboard().set_contents(ClipboardKind::Clipboard, s.render_string.string()[highlight_range].to_string());
+                        }
+                    }
+                },
+                _ => ()
+            }
+        }
+
+        if let ContentsInner::SelectableText(ref s) = self.contents {
+            let highlight_range = s.highlight_range();
+            if highlight_range != old_highlight_range && highlight_range.len() > 0 {
+                context.clipboard().set_contents(ClipboardKind::Primary, s.render_string.string()[highlight_range].to_string());
+            }
+        }
+
         EventOps {
-            action: None,
-            focus: None,
+            action,
+            focus,
             bubble: true,
             cursor_pos: None,
-            cursor_icon: None,
+            cursor_icon,
+            ime_caret_rect: None,
             popup: None
         }
     }
@@ -1,21 +1,25 @@
 //! The core set of widgets provided by Derin to create GUIs.
 
 mod button;
+mod canvas;
 mod direct_render;
 mod edit_box;
 mod group;
 mod label;
+mod spin_box;
 
 pub use self::button::*;
+pub use self::canvas::*;
 pub use self::direct_render::*;
 pub use self::edit_box::*;
 pub use self::group::*;
 pub use self::label::*;
+pub use self::spin_box::*;
 
-use gl_render::{Prim, ThemedPrim, RenderString, RelPoint};
+use gl_render::{Prim, ThemedPrim, RenderString, EditString, RelPoint};
 use cgmath::Point2;
 use cgmath_geometry::DimsBox;
-use theme::Theme;
+use theme::{Theme, LineWrap};
 use core::render::Theme as CoreTheme;
 
 /// The `Widget` trait, as well as associated types used to create custom widgets.
@@ -36,6 +40,8 @@ pub enum Contents<C=String> {
 #[derive(Debug, Clone)]
 enum ContentsInner {
     Text(RenderString),
+    /// Text that the user can select and copy, but not edit.
+    SelectableText(EditString),
     Image(String)
 }
 
@@ -65,7 +71,7 @@ impl Contents<String> {
 }
 
 impl ContentsInner {
-    fn to_prim<D>(&mut self, background_name: &str) -> ThemedPrim<D> {
+    fn to_prim<'a, D>(&'a mut self, background_name: &'a str) -> ThemedPrim<'a, D> {
         match *self {
             ContentsInner::Text(ref mut s) => ThemedPrim {
                 theme_path: background_name,
@@ -79,6 +85,18 @@ impl ContentsInner {
                 ),
                 prim: Prim::String(s),
             },
+            ContentsInner::SelectableText(ref mut s) => ThemedPrim {
+                theme_path: background_name,
+                min: Point2::new(
+                    RelPoint::new(-1.0, 0),
+                    RelPoint::new(-1.0, 0),
+                ),
+                max: Point2::new(
+                    RelPoint::new( 1.0, 0),
+                    RelPoint::new( 1.0, 0)
+                ),
+                prim: Prim::EditString(s),
+            },
             ContentsInner::Image(ref i) => ThemedPrim {
                 theme_path: &**i,
                 min: Point2::new(
@@ -97,6 +115,7 @@ impl ContentsInner {
     fn borrow(&self) -> Contents<&str> {
         match *self {
             ContentsInner::Text(ref t) => Contents::Text(t.string()),
+            ContentsInner::SelectableText(ref t) => Contents::Text(t.render_string.string()),
             ContentsInner::Image(ref s) => Contents::Image(s)
         }
     }
@@ -104,13 +123,56 @@ impl ContentsInner {
     fn borrow_mut(&mut self) -> Contents<&mut String> {
         match *self {
             ContentsInner::Text(ref mut t) => Contents::Text(t.string_mut()),
+            ContentsInner::SelectableText(ref mut t) => {
+                // The selection may not be valid for the new string, so throw it away.
+                t.deselect_all();
+                *t.cursor_pos_mut() = 0;
+                Contents::Text(t.render_string.string_mut())
+            },
             ContentsInner::Image(ref mut s) => Contents::Image(s)
         }
     }
 
+    fn render_string(&self) -> Option<&RenderString> {
+        match *self {
+            ContentsInner::Text(ref s) => Some(s),
+            ContentsInner::SelectableText(ref s) => Some(&s.render_string),
+            ContentsInner::Image(_) => None
+        }
+    }
+
+    fn render_string_mut(&mut self) -> Option<&mut RenderString> {
+        match *self {
+            ContentsInner::Text(ref mut s) => Some(s),
+            ContentsInner::SelectableText(ref mut s) => Some(&mut s.render_string),
+            ContentsInner::Image(_) => None
+        }
+    }
+
+    /// Measure the minimum size of the contents with the theme, without drawing them. Wrapped
+    /// text has no minimum width, and its minimum height is the height of its unwrapped lines.
+    fn measure(&self, theme: &Theme, theme_path: &str) -> DimsBox<Point2<i32>> {
+        let render_string = match self.render_string() {
+            Some(s) => s,
+            None => return self.min_size(theme)
+        };
+
+        match theme.widget_theme(theme_path).text {
+            Some(text_style) => {
+                let size = theme.text_metrics().measure(render_string, &text_style, None);
+                match text_style.line_wrap {
+                    LineWrap::None => size,
+                    _ => DimsBox::new2(0, size.height())
+                }
+            },
+            None => DimsBox::new2(0, 0)
+        }
+    }
+
     fn min_size(&self, theme: &Theme) -> DimsBox<Point2<i32>> {
         match *self {
             ContentsInner::Text(ref s) => s.min_size(),
+            ContentsInner::SelectableText(ref s) => s.render_string.min_size(),
             ContentsInner::Image(ref i) => theme.widget_theme(&**i).image.as_ref().and_then(|img| img.dims.cast()).unwrap_or(DimsBox::new2(0, 0))
         }
     }
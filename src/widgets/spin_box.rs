@@ -0,0 +1,534 @@
+use core::event::{EventOps, EventContext, WidgetEvent, InputState, FocusChange};
+use core::tree::{WidgetIdent, UpdateTag, WidgetSubtrait, WidgetSubtraitMut, Widget};
+use core::render::{FrameRectStack, Theme};
+use core::popup::ChildPopupsMut;
+use core::timer::TimerRegister;
+
+use cgmath::Point2;
+use cgmath_geometry::{BoundBox, GeoBox, Segment};
+use dct::layout::SizeBounds;
+use dct::cursor::CursorIcon;
+use dct::buttons::{Key, ModifierKeys, MouseButton};
+
+use gl_render::{ThemedPrim, PrimFrame, RenderString, EditString, RelPoint, Prim};
+
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::Duration;
+
+use arrayvec::ArrayVec;
+
+/// The width, in pixels, of the column containing the up and down buttons.
+const SPIN_BUTTON_WIDTH: i32 = 16;
+/// The number of pixels a pixel-precise scroll, such as from a touchpad, has to move to step the
+/// value once.
+const SCROLL_PX_PER_STEP: i32 = 20;
+
+/// A numeric type that can be edited with a `SpinBox`.
+pub trait SpinValue: 'static + Copy + PartialOrd + FromStr + Display {
+    fn min_value() -> Self;
+    fn max_value() -> Self;
+    /// The default amount to step the value by.
+    fn default_step() -> Self;
+    /// The default number of digits to display after the decimal point.
+    fn default_precision() -> usize;
+    fn saturating_add(self, rhs: Self) -> Self;
+    fn saturating_sub(self, rhs: Self) -> Self;
+    /// Multiply the value by `steps`, saturating at the type's bounds.
+    fn saturating_mul_steps(self, steps: u32) -> Self;
+}
+
+pub trait SpinBoxHandler<T: SpinValue> {
+    type Action: 'static;
+
+    fn on_value_changed(&mut self, value: T) -> Option<Self::Action>;
+}
+
+impl<T: SpinValue, A: 'static + Clone> SpinBoxHandler<T> for Option<A> {
+    type Action = A;
+
+    fn on_value_changed(&mut self, _: T) -> Option<Self::Action> {
+        self.clone()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum SpinButton {
+    Up,
+    Down
+}
+
+#[derive(Debug, Clone)]
+pub struct SpinBox<T: SpinValue, H: SpinBoxHandler<T>> {
+    update_tag: UpdateTag,
+    bounds: BoundBox<Point2<i32>>,
+    value: T,
+    min: T,
+    max: T,
+    step: T,
+    precision: usize,
+    string: EditString,
+    up_string: RenderString,
+    down_string: RenderString,
+    hover_button: Option<SpinButton>,
+    pressed_button: Option<SpinButton>,
+    /// Pixels scrolled that haven't added up to a full step yet.
+    scroll_px: i32,
+    handler: H,
+    size_bounds: SizeBounds
+}
+
+impl<T: SpinValue, H: SpinBoxHandler<T>> SpinBox<T, H> {
+    pub fn new(value: T, handler: H) -> SpinBox<T, H> {
+        let mut spin_box = SpinBox {
+            update_tag: UpdateTag::new(),
+            bounds: BoundBox::new2(0, 0, 0, 0),
+            value,
+            min: T::min_value(),
+            max: T::max_value(),
+            step: T::default_step(),
+            precision: T::default_precision(),
+            string: EditString::new(RenderString::new(String::new())),
+            up_string: RenderString::new("▲".to_string()),
+            down_string: RenderString::new("▼".to_string()),
+            hover_button: None,
+            pressed_button: None,
+            scroll_px: 0,
+            handler,
+            size_bounds: SizeBounds::default()
+        };
+        spin_box.value = spin_box.normalize(value);
+        spin_box.reset_string();
+        spin_box
+    }
+
+    pub fn value(&self) -> T {
+        self.value
+    }
+
+    /// Set the value, clamping it to the spin box's range.
+    pub fn set_value(&mut self, value: T) {
+        self.value = self.normalize(value);
+        self.reset_string();
+        self.update_tag.mark_render_self();
+    }
+
+    pub fn min(&self) -> T {
+        self.min
+    }
+
+    pub fn max(&self) -> T {
+        self.max
+    }
+
+    /// Set the range of allowed values, clamping the current value to that range.
+    pub fn set_range(&mut self, min: T, max: T) {
+        self.min = min;
+        self.max = max;
+        let value = self.value;
+        self.set_value(value);
+    }
+
+    pub fn step(&self) -> T {
+        self.step
+    }
+
+    /// Set the amount the value changes when stepping up or down.
+    pub fn set_step(&mut self, step: T) {
+        self.step = step;
+    }
+
+    pub fn precision(&self) -> usize {
+        self.precision
+    }
+
+    /// Set the number of digits displayed after the decimal point. This is ignored for integer
+    /// types.
+    pub fn set_precision(&mut self, precision: usize) {
+        self.precision = precision;
+        let value = self.value;
+        self.set_value(value);
+    }
+
+    /// Clamp the value to the spin box's range, and round it to the displayed precision.
+    fn normalize(&self, value: T) -> T {
+        let clamped = match () {
+            _ if value < self.min => self.min,
+            _ if self.max < value => self.max,
+            _ => value
+        };
+        format!("{:.*}", self.precision, clamped).parse().unwrap_or(clamped)
+    }
+
+    fn reset_string(&mut self) {
+        *self.string.render_string.string_mut() = format!("{:.*}", self.precision, self.value);
+        self.string.deselect_all();
+        *self.string.cursor_pos_mut() = self.string.render_string.string().len();
+    }
+
+    /// Change the value to `new_value`, returning the handler's action if the value changed.
+    fn change_value(&mut self, new_value: T) -> Option<H::Action> {
+        let new_value = self.normalize(new_value);
+        let value_changed = new_value != self.value;
+        self.value = new_value;
+        self.reset_string();
+        self.update_tag.mark_render_self();
+
+        match value_changed {
+            true => self.handler.on_value_changed(new_value),
+            false => None
+        }
+    }
+
+    fn step_value(&mut self, steps: i32) -> Option<H::Action> {
+        // Widened first, since `i32::MIN` has no positive `i32` counterpart.
+        let step = self.step.saturating_mul_steps((steps as i64).abs() as u32);
+        let new_value = match steps < 0 {
+            false => self.value.saturating_add(step),
+            true => self.value.saturating_sub(step)
+        };
+        self.change_value(new_value)
+    }
+
+    /// Parse the typed text and clamp it to the spin box's range. If the text can't be parsed, it's
+    /// reset to the current value.
+    fn commit(&mut self) -> Option<H::Action> {
+        match self.string.render_string.string().trim().parse() {
+            Ok(new_value) => self.change_value(new_value),
+            Err(_) => {
+                self.reset_string();
+                self.update_tag.mark_render_self();
+                None
+            }
+        }
+    }
+
+    fn button_at(&self, pos: Point2<i32>) -> Option<SpinButton> {
+        let (width, height) = (self.bounds.width(), self.bounds.height());
+        match pos.x >= width - SPIN_BUTTON_WIDTH {
+            true if pos.y < height / 2 => Some(SpinButton::Up),
+            true => Some(SpinButton::Down),
+            false => None
+        }
+    }
+
+    fn button_theme_path(&self, button: SpinButton) -> &'static str {
+        match (self.pressed_button == Some(button), self.hover_button == Some(button)) {
+            (true, _) => "Button::Clicked",
+            (false, true) => "Button::Hover",
+            (false, false) => "Button::Normal"
+        }
+    }
+}
+
+impl<F, T, H> Widget<H::Action, F> for SpinBox<T, H>
+    where F: PrimFrame,
+          T: SpinValue,
+          H: SpinBoxHandler<T>
+{
+    #[inline]
+    fn update_tag(&self) -> &UpdateTag {
+        &self.update_tag
+    }
+
+    #[inline]
+    fn rect(&self) -> BoundBox<Point2<i32>> {
+        self.bounds
+    }
+
+    #[inline]
+    fn rect_mut(&mut self) -> &mut BoundBox<Point2<i32>> {
+        &mut self.bounds
+    }
+
+    #[inline]
+    fn size_bounds(&self) -> SizeBounds {
+        self.size_bounds
+    }
+
+    fn update_size_bounds(&mut self, theme: &F::Theme) {
+        let widget_theme = theme.widget_theme("EditBox");
+        self.size_bounds.min = widget_theme.min_size();
+        self.size_bounds.min.dims.x += SPIN_BUTTON_WIDTH;
+        if let Some(ref text_style) = widget_theme.text {
+            self.size_bounds.min.dims.y += theme.text_metrics().line_height(text_style);
+        }
+    }
+
+    fn render(&mut self, frame: &mut FrameRectStack<F>) {
+        let up_theme_path = self.button_theme_path(SpinButton::Up);
+        let down_theme_path = self.button_theme_path(SpinButton::Down);
+
+        let field_min = Point2::new(
+            RelPoint::new(-1.0, 0),
+            RelPoint::new(-1.0, 0),
+        );
+        let field_max = Point2::new(
+            RelPoint::new( 1.0, -SPIN_BUTTON_WIDTH),
+            RelPoint::new( 1.0, 0)
+        );
+        let up_min = Point2::new(
+            RelPoint::new( 1.0, -SPIN_BUTTON_WIDTH),
+            RelPoint::new(-1.0, 0),
+        );
+        let up_max = Point2::new(
+            RelPoint::new( 1.0, 0),
+            RelPoint::new( 0.0, 0)
+        );
+        let down_min = Point2::new(
+            RelPoint::new( 1.0, -SPIN_BUTTON_WIDTH),
+            RelPoint::new( 0.0, 0),
+        );
+        let down_max = Point2::new(
+            RelPoint::new( 1.0, 0),
+            RelPoint::new( 1.0, 0)
+        );
+
+        frame.upload_primitives(ArrayVec::from([
+            ThemedPrim {
+                theme_path: "EditBox",
+                min: field_min,
+                max: field_max,
+                prim: Prim::Image
+            },
+            ThemedPrim {
+                theme_path: "EditBox",
+                min: field_min,
+                max: field_max,
+                prim: Prim::EditString(&mut self.string)
+            },
+            ThemedPrim {
+                theme_path: up_theme_path,
+                min: up_min,
+                max: up_max,
+                prim: Prim::Image
+            },
+            ThemedPrim {
+                theme_path: up_theme_path,
+                min: up_min,
+                max: up_max,
+                prim: Prim::String(&mut self.up_string)
+            },
+            ThemedPrim {
+                theme_path: down_theme_path,
+                min: down_min,
+                max: down_max,
+                prim: Prim::Image
+            },
+            ThemedPrim {
+                theme_path: down_theme_path,
+                min: down_min,
+                max: down_max,
+                prim: Prim::String(&mut self.down_string)
+            }
+        ]).into_iter());
+    }
+
+    fn on_widget_event(&mut self, event: WidgetEvent, _: InputState, _: &mut EventContext, _: Option<ChildPopupsMut<H::Action, F>>, _: &[WidgetIdent]) -> EventOps<H::Action, F> {
+        use self::WidgetEvent::*;
+
+        let allow_char = |c: char| c.is_digit(10) || c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E';
+        let mut action = None;
+        let mut focus = None;
+        let mut cursor_icon = None;
+        match event {
+            KeyDown(key, modifiers) => loop {
+                let jump_to_word_boundaries = modifiers.contains(ModifierKeys::CTRL);
+                match (key, modifiers) {
+                    (Key::UArrow, _) => action = self.step_value(1),
+                    (Key::DArrow, _) => action = self.step_value(-1),
+                    (Key::LArrow, _) => self.string.move_cursor_visual(
+                        -1,
+                        jump_to_word_boundaries,
+                        modifiers.contains(ModifierKeys::SHIFT)
+                    ),
+                    (Key::RArrow, _) => self.string.move_cursor_visual(
+                        1,
+                        jump_to_word_boundaries,
+                        modifiers.contains(ModifierKeys::SHIFT)
+                    ),
+                    (Key::A, ModifierKeys::CTRL) => self.string.select_all(),
+                    (Key::Back, _) => self.string.delete_chars(-1, jump_to_word_boundaries),
+                    (Key::Delete, _) => self.string.delete_chars(1, jump_to_word_boundaries),
+                    (Key::Enter, _) => action = self.commit(),
+                    _ => break
+                }
+                self.update_tag
+                    .mark_render_self()
+                    .mark_update_timer();
+                break;
+            },
+            Char(c) if allow_char(c) => {
+                self.string.insert_char(c);
+                self.update_tag
+                    .mark_render_self()
+                    .mark_update_timer();
+            },
+            MouseScrollLines(dir) => action = self.step_value(dir.y),
+            MouseScrollPx(dir) => {
+                self.scroll_px += dir.y;
+                let steps = self.scroll_px / SCROLL_PX_PER_STEP;
+                self.scroll_px -= steps * SCROLL_PX_PER_STEP;
+                if steps != 0 {
+                    action = self.step_value(steps);
+                }
+            },
+            MouseDown{in_widget: true, button: MouseButton::Left, pos} => {
+                focus = Some(FocusChange::Take);
+                match self.button_at(pos) {
+                    Some(spin_button) => {
+                        self.pressed_button = Some(spin_button);
+                        action = match spin_button {
+                            SpinButton::Up => self.step_value(1),
+                            SpinButton::Down => self.step_value(-1)
+                        };
+                    },
+                    None => self.string.select_on_line(Segment::new(pos, pos))
+                }
+                self.update_tag
+                    .mark_render_self()
+                    .mark_update_timer();
+            },
+            MouseDown{in_widget: true, ..} => focus = Some(FocusChange::Take),
+            MouseUp{button: MouseButton::Left, ..} => {
+                self.pressed_button = None;
+                self.update_tag.mark_render_self();
+            },
+            MouseDown{in_widget: false, ..} => {
+                focus = Some(FocusChange::Remove);
+                self.string.draw_cursor = false;
+                self.update_tag
+                    .mark_render_self()
+                    .mark_update_timer();
+            },
+            MouseMove{new_pos, buttons_down_in_widget, ..} => {
+                let hover_button = self.button_at(new_pos);
+                if hover_button != self.hover_button {
+                    self.hover_button = hover_button;
+                    cursor_icon = Some(match hover_button {
+                        Some(_) => CursorIcon::default(),
+                        None => CursorIcon::Text
+                    });
+                    self.update_tag.mark_render_self();
+                }
+
+                let field_drag = self.pressed_button.is_none();
+                if let Some(down) = buttons_down_in_widget.iter().find(|d| d.button == MouseButton::Left && field_drag) {
+                    self.string.select_on_line(Segment::new(down.down_pos, new_pos));
+                    self.update_tag.mark_render_self();
+                }
+            },
+            MouseEnter{enter_pos, ..} => {
+                self.hover_button = self.button_at(enter_pos);
+                cursor_icon = Some(match self.hover_button {
+                    Some(_) => CursorIcon::default(),
+                    None => CursorIcon::Text
+                });
+                self.update_tag.mark_render_self();
+            },
+            MouseExit{..} => {
+                self.hover_button = None;
+                cursor_icon = Some(CursorIcon::default());
+                self.update_tag.mark_render_self();
+            },
+            GainFocus => {
+                self.string.deselect_all();
+                self.update_tag.mark_update_timer();
+            },
+            LoseFocus => {
+                self.string.deselect_all();
+                self.string.draw_cursor = false;
+                action = self.commit();
+                self.update_tag
+                    .mark_render_self()
+                    .mark_update_timer();
+            },
+            Timer{name: "cursor_flash", times_triggered, ..} => {
+                self.string.draw_cursor = times_triggered % 2 == 0;
+                self.update_tag.mark_render_self();
+            },
+            _ => ()
+        };
+        EventOps {
+            action,
+            focus,
+            bubble: true,
+            cursor_pos: None,
+            cursor_icon,
+            ime_caret_rect: None,
+            popup: None
+        }
+    }
+
+    fn register_timers(&self, register: &mut TimerRegister) {
+        if self.update_tag.has_keyboard_focus() {
+            register.add_timer("cursor_flash", Duration::new(1, 0)/2, true);
+        }
+    }
+
+    #[inline]
+    fn subtrait(&self) -> WidgetSubtrait<H::Action, F> {
+        WidgetSubtrait::Widget(self)
+    }
+
+    #[inline]
+    fn subtrait_mut(&mut self) -> WidgetSubtraitMut<H::Action, F> {
+        WidgetSubtraitMut::Widget(self)
+    }
+}
+
+macro_rules! spin_value_int {
+    ($($int:ty),+) => {$(
+        impl SpinValue for $int {
+            #[inline]
+            fn min_value() -> $int {<$int>::min_value()}
+            #[inline]
+            fn max_value() -> $int {<$int>::max_value()}
+            #[inline]
+            fn default_step() -> $int {1}
+            #[inline]
+            fn default_precision() -> usize {0}
+            #[inline]
+            fn saturating_add(self, rhs: $int) -> $int {<$int>::saturating_add(self, rhs)}
+            #[inline]
+            fn saturating_sub(self, rhs: $int) -> $int {<$int>::saturating_sub(self, rhs)}
+            #[inline]
+            fn saturating_mul_steps(self, steps: u32) -> $int {
+                match steps as u64 <= <$int>::max_value() as u64 {
+                    true => <$int>::saturating_mul(self, steps as $int),
+                    // `steps` doesn't fit in the type, so any nonzero value overflows.
+                    false => match self.cmp(&0) {
+                        Ordering::Less => <$int>::min_value(),
+                        Ordering::Equal => 0,
+                        Ordering::Greater => <$int>::max_value()
+                    }
+                }
+            }
+        }
+    )+}
+}
+
+macro_rules! spin_value_float {
+    ($($float:ident),+) => {$(
+        impl SpinValue for $float {
+            #[inline]
+            fn min_value() -> $float {::std::$float::MIN}
+            #[inline]
+            fn max_value() -> $float {::std::$float::MAX}
+            #[inline]
+            fn default_step() -> $float {1.0}
+            #[inline]
+            fn default_precision() -> usize {2}
+            #[inline]
+            fn saturating_add(self, rhs: $float) -> $float {self + rhs}
+            #[inline]
+            fn saturating_sub(self, rhs: $float) -> $float {self - rhs}
+            #[inline]
+            fn saturating_mul_steps(self, steps: u32) -> $float {self * steps as $float}
+        }
+    )+}
+}
+
+spin_value_int!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+spin_value_float!(f32, f64);